@@ -1,4 +1,6 @@
-use crate::scanner::token::Token;
+use std::rc::Rc;
+
+use crate::scanner::token::{Span, Token};
 
 pub trait AstVisitor<R, C> {
     fn process(&mut self, expr: &Expr, context: Option<&C>) -> R;
@@ -7,17 +9,71 @@ pub trait AstVisitor<R, C> {
     fn visit_unary(&mut self, unary: &Unary, context: Option<&C>) -> R;
     fn visit_grouping(&mut self, grouping: &Grouping, context: Option<&C>) -> R;
     fn visit_literal(&mut self, literal: &Literal, context: Option<&C>) -> R;
+    fn visit_variable(&mut self, variable: &Variable, context: Option<&C>) -> R;
+    fn visit_assign(&mut self, assign: &Assign, context: Option<&C>) -> R;
+    fn visit_logical(&mut self, logical: &Logical, context: Option<&C>) -> R;
+    fn visit_call(&mut self, call: &Call, context: Option<&C>) -> R;
+    fn visit_get(&mut self, get: &Get, context: Option<&C>) -> R;
+    fn visit_set(&mut self, set: &Set, context: Option<&C>) -> R;
+    fn visit_this(&mut self, this: &This, context: Option<&C>) -> R;
+    fn visit_super(&mut self, sup: &Super, context: Option<&C>) -> R;
 }
 
 pub trait Walkable {
     fn accept<R, C>(&self, visitor: &mut dyn AstVisitor<R, C>, context: Option<&C>) -> R;
 }
 
+pub trait StmtVisitor<R, C> {
+    fn process_stmt(&mut self, stmt: &Stmt, context: Option<&C>) -> R;
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression, context: Option<&C>) -> R;
+    fn visit_print_stmt(&mut self, stmt: &Print, context: Option<&C>) -> R;
+    fn visit_var_stmt(&mut self, stmt: &Var, context: Option<&C>) -> R;
+    fn visit_block_stmt(&mut self, stmt: &Block, context: Option<&C>) -> R;
+    fn visit_if_stmt(&mut self, stmt: &If, context: Option<&C>) -> R;
+    fn visit_while_stmt(&mut self, stmt: &While, context: Option<&C>) -> R;
+    fn visit_function_stmt(&mut self, stmt: &Function, context: Option<&C>) -> R;
+    fn visit_return_stmt(&mut self, stmt: &Return, context: Option<&C>) -> R;
+    fn visit_class_stmt(&mut self, stmt: &Class, context: Option<&C>) -> R;
+}
+
+pub trait StmtWalkable {
+    fn accept<R, C>(&self, visitor: &mut dyn StmtVisitor<R, C>, context: Option<&C>) -> R;
+}
+
 pub enum Expr {
     Binary(Binary),
     Unary(Unary),
     Grouping(Grouping),
     Literal(Literal),
+    Variable(Variable),
+    Assign(Assign),
+    Logical(Logical),
+    Call(Call),
+    Get(Get),
+    Set(Set),
+    This(This),
+    Super(Super),
+}
+
+impl Expr {
+    /// The region of source this expression was parsed from.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary(val) => val.span,
+            Expr::Unary(val) => val.span,
+            Expr::Grouping(val) => val.span,
+            Expr::Literal(val) => val.span,
+            Expr::Variable(val) => val.name.span,
+            Expr::Assign(val) => val.name.span.to(&val.value.span()),
+            Expr::Logical(val) => val.left.span().to(&val.right.span()),
+            Expr::Call(val) => val.callee.span().to(&val.paren.span),
+            Expr::Get(val) => val.object.span().to(&val.name.span),
+            Expr::Set(val) => val.object.span().to(&val.value.span()),
+            Expr::This(val) => val.keyword.span,
+            Expr::Super(val) => val.keyword.span.to(&val.method.span),
+        }
+    }
 }
 
 impl Walkable for Expr {
@@ -27,6 +83,14 @@ impl Walkable for Expr {
             Expr::Unary(val) => visitor.visit_unary(val, context),
             Expr::Grouping(val) => visitor.visit_grouping(val, context),
             Expr::Literal(val) => visitor.visit_literal(val, context),
+            Expr::Variable(val) => visitor.visit_variable(val, context),
+            Expr::Assign(val) => visitor.visit_assign(val, context),
+            Expr::Logical(val) => visitor.visit_logical(val, context),
+            Expr::Call(val) => visitor.visit_call(val, context),
+            Expr::Get(val) => visitor.visit_get(val, context),
+            Expr::Set(val) => visitor.visit_set(val, context),
+            Expr::This(val) => visitor.visit_this(val, context),
+            Expr::Super(val) => visitor.visit_super(val, context),
         }
     }
 }
@@ -35,14 +99,17 @@ pub struct Binary {
     pub left: Box<Expr>,
     pub operator: Token,
     pub right: Box<Expr>,
+    pub span: Span,
 }
 
 impl Binary {
     pub fn new(left: Box<Expr>, operator: Token, right: Box<Expr>) -> Self {
+        let span = left.span().to(&right.span());
         Self {
-            left: left,
-            operator: operator,
-            right: right,
+            left,
+            operator,
+            right,
+            span,
         }
     }
 }
@@ -50,36 +117,326 @@ impl Binary {
 pub struct Unary {
     pub operator: Token,
     pub right: Box<Expr>,
+    pub span: Span,
 }
 
 impl Unary {
     pub fn new(operator: Token, right: Box<Expr>) -> Self {
+        let span = operator.span.to(&right.span());
         Self {
-            operator: operator,
-            right: right,
+            operator,
+            right,
+            span,
         }
     }
 }
 
 pub struct Grouping {
     pub expression: Box<Expr>,
+    // Covers the parentheses as well as the inner expression
+    pub span: Span,
 }
 
 impl Grouping {
-    pub fn new(expression: Box<Expr>) -> Self {
+    pub fn new(expression: Box<Expr>, span: Span) -> Self {
         Self {
-            expression: expression,
+            expression,
+            span,
         }
     }
 }
 
-pub enum Literal {
+pub struct Literal {
+    pub value: LiteralValue,
+    pub span: Span,
+}
+
+impl Literal {
+    pub fn new(value: LiteralValue, span: Span) -> Self {
+        Self {
+            value,
+            span,
+        }
+    }
+}
+
+pub enum LiteralValue {
     Number(f64),
     String(String),
     Boolean(bool),
     Nil,
 }
 
+pub struct Variable {
+    pub name: Token,
+}
+
+impl Variable {
+    pub fn new(name: Token) -> Self {
+        Self {
+            name,
+        }
+    }
+}
+
+pub struct Assign {
+    pub name: Token,
+    pub value: Box<Expr>,
+}
+
+impl Assign {
+    pub fn new(name: Token, value: Box<Expr>) -> Self {
+        Self {
+            name,
+            value,
+        }
+    }
+}
+
+pub struct Logical {
+    pub left: Box<Expr>,
+    pub operator: Token,
+    pub right: Box<Expr>,
+}
+
+impl Logical {
+    pub fn new(left: Box<Expr>, operator: Token, right: Box<Expr>) -> Self {
+        Self {
+            left,
+            operator,
+            right,
+        }
+    }
+}
+
+pub struct Call {
+    pub callee: Box<Expr>,
+    pub paren: Token,
+    pub arguments: Vec<Expr>,
+}
+
+impl Call {
+    pub fn new(callee: Box<Expr>, paren: Token, arguments: Vec<Expr>) -> Self {
+        Self {
+            callee,
+            paren,
+            arguments,
+        }
+    }
+}
+
+pub struct Get {
+    pub object: Box<Expr>,
+    pub name: Token,
+}
+
+impl Get {
+    pub fn new(object: Box<Expr>, name: Token) -> Self {
+        Self {
+            object,
+            name,
+        }
+    }
+}
+
+pub struct Set {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub value: Box<Expr>,
+}
+
+impl Set {
+    pub fn new(object: Box<Expr>, name: Token, value: Box<Expr>) -> Self {
+        Self {
+            object,
+            name,
+            value,
+        }
+    }
+}
+
+pub struct This {
+    pub keyword: Token,
+}
+
+impl This {
+    pub fn new(keyword: Token) -> Self {
+        Self {
+            keyword,
+        }
+    }
+}
+
+pub struct Super {
+    pub keyword: Token,
+    pub method: Token,
+}
+
+impl Super {
+    pub fn new(keyword: Token, method: Token) -> Self {
+        Self {
+            keyword,
+            method,
+        }
+    }
+}
+
+pub enum Stmt {
+    Expression(Expression),
+    Print(Print),
+    Var(Var),
+    Block(Block),
+    If(If),
+    While(While),
+    Function(Function),
+    Return(Return),
+    Class(Class),
+}
+
+impl StmtWalkable for Stmt {
+    fn accept<R, C>(&self, visitor: &mut dyn StmtVisitor<R, C>, context: Option<&C>) -> R {
+        match self {
+            Stmt::Expression(val) => visitor.visit_expression_stmt(val, context),
+            Stmt::Print(val) => visitor.visit_print_stmt(val, context),
+            Stmt::Var(val) => visitor.visit_var_stmt(val, context),
+            Stmt::Block(val) => visitor.visit_block_stmt(val, context),
+            Stmt::If(val) => visitor.visit_if_stmt(val, context),
+            Stmt::While(val) => visitor.visit_while_stmt(val, context),
+            Stmt::Function(val) => visitor.visit_function_stmt(val, context),
+            Stmt::Return(val) => visitor.visit_return_stmt(val, context),
+            Stmt::Class(val) => visitor.visit_class_stmt(val, context),
+        }
+    }
+}
+
+pub struct Expression {
+    pub expression: Box<Expr>,
+}
+
+impl Expression {
+    pub fn new(expression: Box<Expr>) -> Self {
+        Self {
+            expression,
+        }
+    }
+}
+
+pub struct Print {
+    pub expression: Box<Expr>,
+}
+
+impl Print {
+    pub fn new(expression: Box<Expr>) -> Self {
+        Self {
+            expression,
+        }
+    }
+}
+
+pub struct Var {
+    pub name: Token,
+    pub initializer: Option<Box<Expr>>,
+}
+
+impl Var {
+    pub fn new(name: Token, initializer: Option<Box<Expr>>) -> Self {
+        Self {
+            name,
+            initializer,
+        }
+    }
+}
+
+pub struct Block {
+    pub statements: Vec<Stmt>,
+}
+
+impl Block {
+    pub fn new(statements: Vec<Stmt>) -> Self {
+        Self {
+            statements,
+        }
+    }
+}
+
+pub struct If {
+    pub condition: Box<Expr>,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+}
+
+impl If {
+    pub fn new(condition: Box<Expr>, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>>) -> Self {
+        Self {
+            condition,
+            then_branch,
+            else_branch,
+        }
+    }
+}
+
+pub struct While {
+    pub condition: Box<Expr>,
+    pub body: Box<Stmt>,
+}
+
+impl While {
+    pub fn new(condition: Box<Expr>, body: Box<Stmt>) -> Self {
+        Self {
+            condition,
+            body,
+        }
+    }
+}
+
+// The body is shared so function values can outlive the parsed program
+#[derive(Clone)]
+pub struct Function {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Rc<Vec<Stmt>>,
+}
+
+impl Function {
+    pub fn new(name: Token, params: Vec<Token>, body: Vec<Stmt>) -> Self {
+        Self {
+            name,
+            params,
+            body: Rc::new(body),
+        }
+    }
+}
+
+pub struct Return {
+    pub keyword: Token,
+    pub value: Option<Box<Expr>>,
+}
+
+impl Return {
+    pub fn new(keyword: Token, value: Option<Box<Expr>>) -> Self {
+        Self {
+            keyword,
+            value,
+        }
+    }
+}
+
+pub struct Class {
+    pub name: Token,
+    pub superclass: Option<Variable>,
+    pub methods: Vec<Function>,
+}
+
+impl Class {
+    pub fn new(name: Token, superclass: Option<Variable>, methods: Vec<Function>) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+}
+
 pub struct AstPrinter;
 impl AstVisitor<String, ()> for AstPrinter {
     fn process(&mut self, expr: &Expr, context: Option<&()>) -> String {
@@ -87,29 +444,138 @@ impl AstVisitor<String, ()> for AstPrinter {
     }
 
     fn visit_binary(&mut self, binary: &Binary, context: Option<&()>) -> String {
-        print!("binary\n");
         let operator = binary.operator.lexeme.as_ref().unwrap();
         let left = binary.left.accept(self, context);
         let right = binary.right.accept(self, context);
         format!("({} {} {})", operator, left, right)
     }
     fn visit_unary(&mut self, unary: &Unary, context: Option<&()>) -> String {
-        print!("unary\n");
         let operator = unary.operator.lexeme.as_ref().unwrap();
         let right = unary.right.accept(self, context);
         format!("({} {})", operator, right)
     }
     fn visit_grouping(&mut self, grouping: &Grouping, context: Option<&()>) -> String {
-        print!("group\n");
         format!("(group {})", grouping.expression.accept(self, context))
     }
     fn visit_literal(&mut self, literal: &Literal, _: Option<&()>) -> String {
-        print!("lit\n");
-        match literal {
-            Literal::Boolean(val) => val.to_string(),
-            Literal::Number(val) => val.to_string(),
-            Literal::String(val) => val.to_string(),
-            Literal::Nil => String::from("nil"),
+        match &literal.value {
+            LiteralValue::Boolean(val) => val.to_string(),
+            LiteralValue::Number(val) => val.to_string(),
+            LiteralValue::String(val) => val.to_string(),
+            LiteralValue::Nil => String::from("nil"),
+        }
+    }
+    fn visit_variable(&mut self, variable: &Variable, _: Option<&()>) -> String {
+        variable.name.lexeme.clone().unwrap_or_default()
+    }
+    fn visit_assign(&mut self, assign: &Assign, context: Option<&()>) -> String {
+        let name = assign.name.lexeme.as_deref().unwrap_or("_");
+        format!("(= {} {})", name, assign.value.accept(self, context))
+    }
+    fn visit_logical(&mut self, logical: &Logical, context: Option<&()>) -> String {
+        let operator = logical.operator.lexeme.as_ref().unwrap();
+        let left = logical.left.accept(self, context);
+        let right = logical.right.accept(self, context);
+        format!("({} {} {})", operator, left, right)
+    }
+    fn visit_call(&mut self, call: &Call, context: Option<&()>) -> String {
+        let callee = call.callee.accept(self, context);
+        let arguments: Vec<String> = call
+            .arguments
+            .iter()
+            .map(|argument| argument.accept(self, context))
+            .collect();
+        format!("(call {} {})", callee, arguments.join(" "))
+    }
+    fn visit_get(&mut self, get: &Get, context: Option<&()>) -> String {
+        let name = get.name.lexeme.as_deref().unwrap_or("_");
+        format!("(. {} {})", get.object.accept(self, context), name)
+    }
+    fn visit_set(&mut self, set: &Set, context: Option<&()>) -> String {
+        let name = set.name.lexeme.as_deref().unwrap_or("_");
+        let object = set.object.accept(self, context);
+        format!("(= (. {} {}) {})", object, name, set.value.accept(self, context))
+    }
+    fn visit_this(&mut self, this: &This, _: Option<&()>) -> String {
+        this.keyword.lexeme.clone().unwrap_or_default()
+    }
+    fn visit_super(&mut self, sup: &Super, _: Option<&()>) -> String {
+        format!("(super {})", sup.method.lexeme.as_deref().unwrap_or("_"))
+    }
+}
+
+impl StmtVisitor<String, ()> for AstPrinter {
+    fn process_stmt(&mut self, stmt: &Stmt, context: Option<&()>) -> String {
+        stmt.accept(self, context)
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression, context: Option<&()>) -> String {
+        format!("(; {})", stmt.expression.accept(self, context))
+    }
+    fn visit_print_stmt(&mut self, stmt: &Print, context: Option<&()>) -> String {
+        format!("(print {})", stmt.expression.accept(self, context))
+    }
+    fn visit_var_stmt(&mut self, stmt: &Var, context: Option<&()>) -> String {
+        let name = stmt.name.lexeme.as_deref().unwrap_or("_");
+        match &stmt.initializer {
+            Some(initializer) => format!("(var {} = {})", name, initializer.accept(self, context)),
+            None => format!("(var {})", name),
+        }
+    }
+    fn visit_block_stmt(&mut self, stmt: &Block, context: Option<&()>) -> String {
+        let statements: Vec<String> = stmt
+            .statements
+            .iter()
+            .map(|statement| statement.accept(self, context))
+            .collect();
+        format!("(block {})", statements.join(" "))
+    }
+    fn visit_if_stmt(&mut self, stmt: &If, context: Option<&()>) -> String {
+        let condition = stmt.condition.accept(self, context);
+        let then_branch = stmt.then_branch.accept(self, context);
+        match &stmt.else_branch {
+            Some(else_branch) => format!("(if {} {} {})", condition, then_branch, else_branch.accept(self, context)),
+            None => format!("(if {} {})", condition, then_branch),
+        }
+    }
+    fn visit_while_stmt(&mut self, stmt: &While, context: Option<&()>) -> String {
+        let condition = stmt.condition.accept(self, context);
+        format!("(while {} {})", condition, stmt.body.accept(self, context))
+    }
+    fn visit_function_stmt(&mut self, stmt: &Function, context: Option<&()>) -> String {
+        let name = stmt.name.lexeme.as_deref().unwrap_or("_");
+        let params: Vec<&str> = stmt
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_deref().unwrap_or("_"))
+            .collect();
+        let body: Vec<String> = stmt
+            .body
+            .iter()
+            .map(|statement| statement.accept(self, context))
+            .collect();
+        format!("(fun {} ({}) {})", name, params.join(" "), body.join(" "))
+    }
+    fn visit_return_stmt(&mut self, stmt: &Return, context: Option<&()>) -> String {
+        let keyword = stmt.keyword.lexeme.as_deref().unwrap_or("_");
+        match &stmt.value {
+            Some(value) => format!("({} {})", keyword, value.accept(self, context)),
+            None => format!("({})", keyword),
+        }
+    }
+    fn visit_class_stmt(&mut self, stmt: &Class, context: Option<&()>) -> String {
+        let name = stmt.name.lexeme.as_deref().unwrap_or("_");
+        let methods: Vec<String> = stmt
+            .methods
+            .iter()
+            .map(|method| self.visit_function_stmt(method, context))
+            .collect();
+        match &stmt.superclass {
+            Some(superclass) => {
+                let superclass = self.visit_variable(superclass, context);
+                format!("(class {} < {} {})", name, superclass, methods.join(" "))
+            }
+            None => format!("(class {} {})", name, methods.join(" ")),
         }
     }
 }
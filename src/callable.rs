@@ -0,0 +1,92 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::tree::Function;
+use crate::class::LoxInstance;
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, RuntimeError, Unwind};
+use crate::scanner::token::{Token, TokenType};
+use crate::value::Value;
+
+pub trait LoxCallable: fmt::Display {
+    fn arity(&self) -> usize;
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError>;
+}
+
+pub struct LoxFunction {
+    declaration: Function,
+    // The scope the function was declared in, kept alive for as long as the function is
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+    // Id of the input the function was declared in, see `Interpreter::add_source`
+    source: usize,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Function,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+        source: usize,
+    ) -> Self {
+        Self {
+            declaration,
+            closure,
+            is_initializer,
+            source,
+        }
+    }
+
+    /// Creates a copy of this method whose `this` refers to `instance`.
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::new(Some(Rc::clone(&self.closure)));
+        environment.define(String::from("this"), Value::Instance(instance));
+        LoxFunction::new(
+            self.declaration.clone(),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+            self.source,
+        )
+    }
+
+    fn this(&self) -> Result<Value, RuntimeError> {
+        let keyword = Token {
+            token_type: TokenType::This,
+            lexeme: Some(String::from("this")),
+            span: self.declaration.name.span,
+        };
+        self.closure.borrow().get_at(0, &keyword)
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let mut environment = Environment::new(Some(Rc::clone(&self.closure)));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme.clone().unwrap_or_default(), argument);
+        }
+
+        // The body may have been typed at an earlier prompt or come from another file
+        let caller_source = interpreter.enter_source(self.source);
+        let result = interpreter.execute_block(&self.declaration.body, Rc::new(RefCell::new(environment)));
+        interpreter.enter_source(caller_source);
+        // An initializer always hands back the instance, even on an early `return;`
+        match result {
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => self.this(),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error.in_source(self.source)),
+        }
+    }
+}
+
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme.as_deref().unwrap_or("_"))
+    }
+}
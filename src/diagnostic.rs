@@ -0,0 +1,244 @@
+use std::fmt::Write;
+use std::io;
+
+use crate::highlight::use_color;
+use crate::interpreter::RuntimeError;
+use crate::parser::ParserErr;
+use crate::scanner::scanner::ScanErr;
+use crate::scanner::token::Span;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// A secondary span, underlined with a short explanation.
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// An input run in a session, kept so errors from code it defined can be shown against it later.
+pub struct Source {
+    pub text: String,
+    // Path of the script, `None` for prompt input
+    pub file: Option<String>,
+}
+
+/// How diagnostics are written out, picked with `--error-format`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    // Source snippets, for people
+    Human,
+    // One JSON object per line, for tools
+    Json,
+}
+
+/// An error ready to be shown against the source it came from.
+pub struct Diagnostic {
+    // Stable identifier of the kind of error, e.g. `parse-error`
+    pub code: &'static str,
+    pub header: String,
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl From<&ScanErr> for Diagnostic {
+    fn from(err: &ScanErr) -> Self {
+        Self {
+            code: "scan-error",
+            header: format!("[line {}] Error: {}", err.span.line, err.message),
+            message: err.message.to_owned(),
+            span: err.span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+}
+
+impl From<&ParserErr> for Diagnostic {
+    fn from(err: &ParserErr) -> Self {
+        Self {
+            code: "parse-error",
+            header: format!("[line {}] Error{}: {}", err.span.line, err.where_err, err.message),
+            message: err.message.to_owned(),
+            span: err.span,
+            labels: Vec::new(),
+            notes: err.notes.clone(),
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(err: &RuntimeError) -> Self {
+        Self {
+            code: "runtime-error",
+            header: format!("{}\n[line {}]", err.message, err.line()),
+            message: err.message.to_owned(),
+            span: err.token.span,
+            labels: err
+                .labels
+                .iter()
+                .map(|(span, message)| Label {
+                    span: *span,
+                    message: message.to_owned(),
+                })
+                .collect(),
+            notes: Vec::new(),
+        }
+    }
+}
+
+/// Writes diagnostics for one source to stderr, either as the header followed by the
+/// offending lines with carets under them, or as JSON.
+pub struct Renderer<'a> {
+    source: &'a str,
+    // Path of the script, `None` for prompt input
+    file: Option<&'a str>,
+    format: ErrorFormat,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    /// Colors the output only when stderr is a terminal and `NO_COLOR` is unset.
+    pub fn new(source: &'a str, file: Option<&'a str>, format: ErrorFormat) -> Self {
+        Self {
+            source,
+            file,
+            format,
+            color: use_color(&io::stderr()),
+        }
+    }
+
+    pub fn emit(&self, diagnostic: &Diagnostic) {
+        match self.format {
+            ErrorFormat::Human => eprint!("{}", self.render(diagnostic)),
+            ErrorFormat::Json => eprintln!("{}", self.to_json(diagnostic)),
+        }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = format!("{}\n", self.paint(RED, &diagnostic.header));
+
+        // The primary span gets carets and no message, the header already says it all
+        let mut marks: Vec<(Span, &str, bool)> = vec![(diagnostic.span, "", true)];
+        marks.extend(
+            diagnostic
+                .labels
+                .iter()
+                .map(|label| (label.span, label.message.as_str(), false)),
+        );
+
+        let mut lines: Vec<usize> = marks.iter().map(|(span, _, _)| span.line).collect();
+        lines.sort_unstable();
+        lines.dedup();
+
+        let width = lines.last().map_or(1, |line| line.to_string().len());
+        let gutter = self.paint(BLUE, &format!("{} |", " ".repeat(width)));
+
+        out.push_str(&format!("{}\n", gutter));
+        for line in lines {
+            let text = self.source.lines().nth(line - 1).unwrap_or("");
+            out.push_str(&format!("{} {}\n", self.paint(BLUE, &format!("{:>width$} |", line)), text));
+
+            for (span, message, primary) in marks.iter().filter(|(span, _, _)| span.line == line) {
+                let (mark, color) = if *primary { ("^", RED) } else { ("-", BLUE) };
+                // Keep tabs so the underline lines up with the source above it
+                let padding: String = text
+                    .chars()
+                    .take(span.column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                let mut marker = mark.repeat(self.underline_width(span));
+                if !message.is_empty() {
+                    marker.push(' ');
+                    marker.push_str(message);
+                }
+                out.push_str(&format!("{} {}{}\n", gutter, padding, self.paint(color, &marker)));
+            }
+        }
+
+        for note in &diagnostic.notes {
+            out.push_str(&format!("{} = {} {}\n", " ".repeat(width), self.paint(BOLD, "note:"), note));
+        }
+
+        out
+    }
+
+    pub fn to_json(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"severity\":\"error\",\"code\":\"{}\",\"message\":{},\"file\":{},{}",
+            diagnostic.code,
+            json_string(&diagnostic.message),
+            self.file.map_or(String::from("null"), json_string),
+            json_location(&diagnostic.span),
+        );
+
+        out.push_str(",\"labels\":[");
+        for (i, label) in diagnostic.labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"message\":{},{}}}",
+                json_string(&label.message),
+                json_location(&label.span)
+            );
+        }
+
+        out.push_str("],\"notes\":[");
+        let notes: Vec<String> = diagnostic.notes.iter().map(|note| json_string(note)).collect();
+        out.push_str(&notes.join(","));
+        out.push_str("]}");
+        out
+    }
+
+    // Spans running past the end of their line are cut at the line break
+    fn underline_width(&self, span: &Span) -> usize {
+        self.source
+            .get(span.start..span.end)
+            .and_then(|text| text.split('\n').next())
+            .map_or(0, |text| text.chars().count())
+            .max(1)
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+// The `line`, `column` and `span` members shared by diagnostics and labels
+fn json_location(span: &Span) -> String {
+    format!(
+        "\"line\":{},\"column\":{},\"span\":{{\"start\":{},\"end\":{}}}",
+        span.line, span.column, span.start, span.end
+    )
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,87 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::interpreter::RuntimeError;
+use crate::scanner::token::Token;
+use crate::value::Value;
+
+pub struct Environment {
+    enclosing: Option<Rc<RefCell<Environment>>>,
+    values: HashMap<String, Value>,
+}
+
+impl Environment {
+    pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Self {
+        Self {
+            enclosing,
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn define(&mut self, name: String, value: Value) {
+        self.values.insert(name, value);
+    }
+
+    /// The names defined directly in this scope with their values, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings: Vec<(String, Value)> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.to_owned(), value.clone()))
+            .collect();
+        bindings.sort_by(|left, right| left.0.cmp(&right.0));
+        bindings
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        let key = name.lexeme.as_deref().unwrap_or("_");
+        if let Some(value) = self.values.get(key) {
+            return Ok(value.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(RuntimeError::new(name, format!("Undefined variable '{}'.", key))),
+        }
+    }
+
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, RuntimeError> {
+        if distance == 0 {
+            let key = name.lexeme.as_deref().unwrap_or("_");
+            return match self.values.get(key) {
+                Some(value) => Ok(value.clone()),
+                None => Err(RuntimeError::new(name, format!("Undefined variable '{}'.", key))),
+            };
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_at(distance - 1, name),
+            None => Err(RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme.as_deref().unwrap_or("_")))),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        let key = name.lexeme.as_deref().unwrap_or("_");
+        if let Some(slot) = self.values.get_mut(key) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(RuntimeError::new(name, format!("Undefined variable '{}'.", key))),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if distance == 0 {
+            return self.assign(name, value);
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => Err(RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme.as_deref().unwrap_or("_")))),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::scanner::token::{Token, TokenType};
+
+const INDENT: &str = "    ";
+
+/// Lays scanned tokens out again as Lox source in a canonical layout.
+/// Works on the token stream rather than the tree, so comments and `for` loops come back as written.
+/// The tokens should already have parsed cleanly.
+pub fn format_tokens(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let mut depth: usize = 0;
+    // Inside parens a ';' separates `for` clauses rather than ending a statement
+    let mut parens: usize = 0;
+    let mut newline_pending = false;
+    // Source line the previous token ended on
+    let mut last_line: usize = 0;
+    let mut prev: Option<&Token> = None;
+    // Whether `prev` was a prefix operator, which hugs its operand
+    let mut prev_unary = false;
+    // Whether a statement has started and not ended yet, so a new line continues it
+    let mut open_statement = false;
+
+    for token in tokens {
+        let text = token.lexeme.as_deref().unwrap_or("");
+        let unary = is_unary(token, prev);
+
+        match &token.token_type {
+            TokenType::Eof => break,
+            // A comment on the line of the code before it stays there, anything else gets its own line
+            TokenType::Comment(_) => {
+                if prev.is_some() && token.span.line == last_line {
+                    out.push(' ');
+                } else {
+                    let indent = depth + usize::from(open_statement);
+                    start_line(&mut out, indent, blank_before(token, prev, last_line));
+                }
+                out.push_str(text.trim_end());
+            }
+            // `{}` for empty bodies, `else` on the line the then branch ends
+            TokenType::RightBrace if is(prev, &TokenType::LeftBrace) => {
+                depth = depth.saturating_sub(1);
+                out.push('}');
+            }
+            TokenType::Else if !matches!(prev, Some(Token { token_type: TokenType::Comment(_), .. })) => {
+                out.push_str(" else")
+            }
+            token_type => {
+                if token_type == &TokenType::RightBrace {
+                    depth = depth.saturating_sub(1);
+                    start_line(&mut out, depth, false);
+                } else if newline_pending {
+                    let indent = depth + usize::from(open_statement);
+                    start_line(&mut out, indent, blank_before(token, prev, last_line));
+                } else if prev.is_some_and(|prev| needs_space(prev, prev_unary, token)) {
+                    out.push(' ');
+                }
+                out.push_str(text);
+            }
+        }
+
+        newline_pending = match &token.token_type {
+            TokenType::Comment(_) | TokenType::LeftBrace | TokenType::RightBrace => true,
+            TokenType::Semicolon => parens == 0,
+            _ => false,
+        };
+        open_statement = match &token.token_type {
+            TokenType::Comment(_) => open_statement,
+            TokenType::LeftBrace | TokenType::RightBrace => false,
+            TokenType::Semicolon => parens > 0,
+            _ => true,
+        };
+        match token.token_type {
+            TokenType::LeftBrace => depth += 1,
+            TokenType::LeftParen => parens += 1,
+            TokenType::RightParen => parens = parens.saturating_sub(1),
+            _ => (),
+        }
+
+        // Strings may span several lines
+        last_line = token.span.line + text.matches('\n').count();
+        prev = Some(token);
+        prev_unary = unary;
+    }
+
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+// Ends the current line, if any, and indents the next one
+fn start_line(out: &mut String, depth: usize, blank: bool) {
+    if !out.is_empty() {
+        out.push('\n');
+        if blank {
+            out.push('\n');
+        }
+    }
+    out.push_str(&INDENT.repeat(depth));
+}
+
+// A run of blank lines in the source is kept as one, except at the start of a block
+fn blank_before(token: &Token, prev: Option<&Token>, last_line: usize) -> bool {
+    token.span.line > last_line + 1 && !is(prev, &TokenType::LeftBrace)
+}
+
+fn needs_space(prev: &Token, prev_unary: bool, token: &Token) -> bool {
+    match (&prev.token_type, &token.token_type) {
+        (_, TokenType::Semicolon | TokenType::Comma | TokenType::Dot | TokenType::RightParen) => false,
+        (TokenType::LeftParen | TokenType::Dot, _) => false,
+        // Calls hug their callee, grouping and keywords keep the space
+        (TokenType::Identifier(_) | TokenType::RightParen, TokenType::LeftParen) => false,
+        _ => !prev_unary,
+    }
+}
+
+// `!` is always a prefix operator; `-` is one unless it follows something that ends an operand
+fn is_unary(token: &Token, prev: Option<&Token>) -> bool {
+    match token.token_type {
+        TokenType::Bang => true,
+        TokenType::Minus => !prev.is_some_and(|prev| {
+            matches!(
+                prev.token_type,
+                TokenType::Identifier(_)
+                    | TokenType::Number(_)
+                    | TokenType::StringLiteral(_)
+                    | TokenType::RightParen
+                    | TokenType::True
+                    | TokenType::False
+                    | TokenType::Nil
+                    | TokenType::This
+            )
+        }),
+        _ => false,
+    }
+}
+
+fn is(token: Option<&Token>, token_type: &TokenType) -> bool {
+    token.is_some_and(|token| &token.token_type == token_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_tokens;
+    use crate::scanner::scanner::Scanner;
+
+    fn format(source: &str) -> String {
+        let scanner = Scanner {
+            source: source.to_owned(),
+        };
+        format_tokens(&scanner.scan_tokens().0)
+    }
+
+    #[test]
+    fn keeps_comments_and_for_loops() {
+        let source = "// sum\nfor (var i = 0; i < 3; i = i + 1) print i; // loop\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn lays_out_blocks() {
+        let source = "fun f(a,b){if(a>-b){return !a;}else{}\n\n\nprint f( 1 ,2 );}";
+        let formatted = "fun f(a, b) {\n    if (a > -b) {\n        return !a;\n    } else {}\n\n    print f(1, 2);\n}\n";
+        assert_eq!(format(source), formatted);
+        assert_eq!(format(formatted), formatted);
+    }
+}
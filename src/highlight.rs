@@ -0,0 +1,83 @@
+use std::env;
+use std::io::IsTerminal;
+
+use crate::scanner::scanner::Scanner;
+use crate::scanner::token::TokenType;
+use crate::value::Value;
+
+const RESET: &str = "\x1b[0m";
+const KEYWORD: &str = "\x1b[1;35m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[36m";
+const COMMENT: &str = "\x1b[90m";
+const IDENTIFIER: &str = "\x1b[34m";
+const LITERAL: &str = "\x1b[33m";
+const ERROR: &str = "\x1b[1;4;31m";
+
+/// Whether output to `stream` should carry ANSI colors: it is a terminal and `NO_COLOR` is unset.
+pub fn use_color(stream: &impl IsTerminal) -> bool {
+    stream.is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+/// Colors `source` token by token, leaving the text itself untouched.
+pub fn highlight_source(source: &str) -> String {
+    let scanner = Scanner {
+        source: source.to_owned(),
+    };
+    let (tokens, _) = scanner.scan_tokens();
+
+    let mut out = String::with_capacity(source.len());
+    // Whitespace between tokens is copied through as-is
+    let mut copied = 0;
+    for token in &tokens {
+        let span = token.span;
+        out.push_str(&source[copied..span.start]);
+
+        let color = match token.token_type {
+            TokenType::And
+            | TokenType::Class
+            | TokenType::Else
+            | TokenType::For
+            | TokenType::Fun
+            | TokenType::If
+            | TokenType::Or
+            | TokenType::Print
+            | TokenType::Return
+            | TokenType::Super
+            | TokenType::This
+            | TokenType::Var
+            | TokenType::While => Some(KEYWORD),
+            TokenType::True | TokenType::False | TokenType::Nil => Some(LITERAL),
+            TokenType::StringLiteral(_) => Some(STRING),
+            TokenType::Number(_) => Some(NUMBER),
+            TokenType::Comment(_) => Some(COMMENT),
+            TokenType::Identifier(_) => Some(IDENTIFIER),
+            // Unknown characters and unterminated strings
+            TokenType::Error => Some(ERROR),
+            _ => None,
+        };
+        out.push_str(&paint(color, &source[span.start..span.end]));
+        copied = span.end;
+    }
+    out.push_str(&source[copied..]);
+    out
+}
+
+/// Formats a value for display, colored by its type.
+pub fn paint_value(value: &Value) -> String {
+    let color = match value {
+        Value::Nil | Value::Bool(_) => Some(LITERAL),
+        Value::Number(_) => Some(NUMBER),
+        Value::String(_) => Some(STRING),
+        Value::Callable(_) | Value::Class(_) => Some(KEYWORD),
+        Value::Instance(_) => Some(IDENTIFIER),
+    };
+    paint(color, &value.to_string())
+}
+
+fn paint(color: Option<&str>, text: &str) -> String {
+    match color {
+        Some(color) if !text.is_empty() => format!("{}{}{}", color, text, RESET),
+        _ => text.to_owned(),
+    }
+}
@@ -1,145 +1,689 @@
-use crate::{ast::tree::*, scanner::token::{Token, TokenType}};
-use std::any::{Any, TypeId};
+use crate::{
+    ast::tree::*,
+    callable::LoxFunction,
+    class::{LoxClass, LoxInstance},
+    diagnostic::Source,
+    environment::Environment,
+    scanner::token::{Span, Token, TokenType},
+    value::Value,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
 
-pub struct Interpreter;
+pub struct RuntimeError {
+    // The offending token, such as the operator of a bad binary
+    pub token: Token,
+    pub message: String,
+    // Secondary spans worth pointing at, such as the operands of a bad binary
+    pub labels: Vec<(Span, String)>,
+    // Id of the input whose function the error escaped from, `None` if it never left the code being run
+    pub source: Option<usize>,
+}
+impl RuntimeError {
+    pub fn new(token: &Token, message: String) -> Self {
+        Self {
+            token: token.clone(),
+            message,
+            labels: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: String) -> Self {
+        self.labels.push((span, message));
+        self
+    }
+
+    /// Marks the error as coming from input `source`, unless an inner function already did.
+    pub fn in_source(mut self, source: usize) -> Self {
+        self.source.get_or_insert(source);
+        self
+    }
+
+    pub fn line(&self) -> usize {
+        self.token.span.line
+    }
+}
 
-fn is_equal(left: Box<dyn Any>, right: Box<dyn Any>) -> bool {
-    if (&*left).type_id() == (&*right).type_id() {
-        let type_id = (&*left).type_id();
-        if type_id == TypeId::of::<f64>() {
-            return left.downcast_ref::<f64>().unwrap() == right.downcast_ref::<f64>().unwrap();
+/// Anything that stops a statement from completing normally.
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+fn check_number_operand(unary: &Unary, operand: &Value) -> Result<f64, RuntimeError> {
+    match operand {
+        Value::Number(val) => Ok(*val),
+        _ => Err(RuntimeError::new(&unary.operator, String::from("Operand must be a number."))
+            .with_label(unary.right.span(), format!("this is {}", operand.type_name()))),
+    }
+}
+
+fn check_number_operands(binary: &Binary, left: &Value, right: &Value) -> Result<(f64, f64), RuntimeError> {
+    match (left, right) {
+        (Value::Number(left_val), Value::Number(right_val)) => Ok((*left_val, *right_val)),
+        _ => Err(operand_error(binary, left, right, "Operands must be numbers.")),
+    }
+}
+
+fn check_arity(paren: &Token, arity: usize, count: usize) -> Result<(), RuntimeError> {
+    if count != arity {
+        return Err(RuntimeError::new(
+            paren,
+            format!("Expected {} arguments but got {}.", arity, count),
+        ));
+    }
+    Ok(())
+}
+
+/// Builds a binary operand error, labelling both operands with their types.
+fn operand_error(binary: &Binary, left: &Value, right: &Value, message: &str) -> RuntimeError {
+    RuntimeError::new(&binary.operator, String::from(message))
+        .with_label(binary.left.span(), format!("this is {}", left.type_name()))
+        .with_label(binary.right.span(), format!("this is {}", right.type_name()))
+}
+
+/// How deep calls may nest before the program fails with "Stack overflow.".
+/// Each call takes a few dozen native frames, so the thread running the interpreter
+/// needs a stack of tens of megabytes to reach this in a debug build.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    // Scope distance of each resolved local reference, keyed by `node_key`
+    locals: HashMap<usize, usize>,
+    // Where `print` writes to
+    output: Box<dyn Write>,
+    // Calls currently in progress
+    call_depth: usize,
+    // Every input run so far, indexed by source id, and the id of the one running now
+    sources: Vec<Source>,
+    source: usize,
+}
+
+/// Identifies an AST node by its address for as long as the node is alive.
+pub fn node_key<T>(node: &T) -> usize {
+    node as *const T as usize
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_output(Box::new(io::stdout()))
+    }
+
+    /// Creates an interpreter whose `print` statements write to `output` instead of stdout.
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new(None)));
+        Self {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            output,
+            call_depth: 0,
+            sources: Vec::new(),
+            source: 0,
         }
-        if type_id == TypeId::of::<bool>() {
-            return left.downcast_ref::<bool>().unwrap() == right.downcast_ref::<bool>().unwrap();
+    }
+
+    pub fn globals(&self) -> Rc<RefCell<Environment>> {
+        Rc::clone(&self.globals)
+    }
+
+    /// Records an input about to be run; functions it declares remember the returned id.
+    pub fn add_source(&mut self, text: &str, file: Option<&str>) -> usize {
+        self.sources.push(Source {
+            text: text.to_owned(),
+            file: file.map(str::to_owned),
+        });
+        self.source = self.sources.len() - 1;
+        self.source
+    }
+
+    pub fn source(&self, id: usize) -> Option<&Source> {
+        self.sources.get(id)
+    }
+
+    /// Attributes code run from now on to input `source`, returning the previous one.
+    pub(crate) fn enter_source(&mut self, source: usize) -> usize {
+        std::mem::replace(&mut self.source, source)
+    }
+
+    pub fn resolve(&mut self, key: usize, depth: Option<usize>) {
+        match depth {
+            Some(depth) => self.locals.insert(key, depth),
+            // Clear anything left behind by a dropped node at the same address
+            None => self.locals.remove(&key),
+        };
+    }
+
+    fn look_up_variable(&self, name: &Token, key: usize) -> Result<Value, RuntimeError> {
+        match self.locals.get(&key) {
+            Some(distance) => self.environment.borrow().get_at(*distance, name),
+            None => self.globals.borrow().get(name),
         }
-        if type_id == TypeId::of::<String>() {
-            return left.downcast_ref::<String>().unwrap().eq(right.downcast_ref::<String>().unwrap());
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        for statement in statements {
+            match self.process_stmt(statement, None) {
+                Ok(()) | Err(Unwind::Return(_)) => (),
+                Err(Unwind::Error(error)) => return Err(error),
+            }
         }
+        Ok(())
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        self.process(expr, None)
+    }
+
+    pub fn execute_block(&mut self, statements: &[Stmt], environment: Rc<RefCell<Environment>>) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        // Restore the outer scope whether or not the block completed
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.process_stmt(statement, None));
+        self.environment = previous;
+        result
     }
-    false
 }
 
-impl AstVisitor<Result<Option<Box<dyn Any>>, String>, ()> for Interpreter {
-    fn process(&mut self, expr: &Expr, context: Option<&()>) -> Result<Option<Box<dyn Any>>, String> {
+impl AstVisitor<Result<Value, RuntimeError>, ()> for Interpreter {
+    fn process(&mut self, expr: &Expr, context: Option<&()>) -> Result<Value, RuntimeError> {
         expr.accept(self, context)
     }
 
-    fn visit_binary(&mut self, binary: &Binary, context: Option<&()>) -> Result<Option<Box<dyn Any>>, String> {
+    fn visit_binary(&mut self, binary: &Binary, context: Option<&()>) -> Result<Value, RuntimeError> {
         let left = binary.left.accept(self, context)?;
         let right = binary.right.accept(self, context)?;
 
         match binary.operator.token_type {
             // Boolean operation
             TokenType::Greater => {
-                return Ok(Some(Box::new(
-                    left.unwrap().downcast_ref::<f64>().unwrap() > right.unwrap().downcast_ref::<f64>().unwrap(),
-                )))
+                let (left_val, right_val) = check_number_operands(binary, &left, &right)?;
+                return Ok(Value::Bool(left_val > right_val));
             }
             TokenType::GreaterEqual => {
-                return Ok(Some(Box::new(
-                    left.unwrap().downcast_ref::<f64>().unwrap() >= right.unwrap().downcast_ref::<f64>().unwrap(),
-                )))
+                let (left_val, right_val) = check_number_operands(binary, &left, &right)?;
+                return Ok(Value::Bool(left_val >= right_val));
             }
             TokenType::Less => {
-                return Ok(Some(Box::new(
-                    left.unwrap().downcast_ref::<f64>().unwrap() < right.unwrap().downcast_ref::<f64>().unwrap(),
-                )))
+                let (left_val, right_val) = check_number_operands(binary, &left, &right)?;
+                return Ok(Value::Bool(left_val < right_val));
             }
             TokenType::LessEqual => {
-                return Ok(Some(Box::new(
-                    left.unwrap().downcast_ref::<f64>().unwrap() <= right.unwrap().downcast_ref::<f64>().unwrap(),
-                )))
-            }
-            
-            TokenType::BangEqual => {
-                return Ok(Some(Box::new(
-                    !is_equal(left.unwrap(), right.unwrap())
-                )))
-            }
-            TokenType::EqualEqual => {
-                return Ok(Some(Box::new(
-                    is_equal(left.unwrap(), right.unwrap())
-                )))
+                let (left_val, right_val) = check_number_operands(binary, &left, &right)?;
+                return Ok(Value::Bool(left_val <= right_val));
             }
 
+            TokenType::BangEqual => return Ok(Value::Bool(left != right)),
+            TokenType::EqualEqual => return Ok(Value::Bool(left == right)),
+
             // Arithmetic operation
             TokenType::Minus => {
-                return Ok(Some(Box::new(
-                    left.unwrap().downcast_ref::<f64>().unwrap() - right.unwrap().downcast_ref::<f64>().unwrap(),
-                )))
+                let (left_val, right_val) = check_number_operands(binary, &left, &right)?;
+                return Ok(Value::Number(left_val - right_val));
             }
             TokenType::Slash => {
-                return Ok(Some(Box::new(
-                    left.unwrap().downcast_ref::<f64>().unwrap() / right.unwrap().downcast_ref::<f64>().unwrap(),
-                )))
+                let (left_val, right_val) = check_number_operands(binary, &left, &right)?;
+                return Ok(Value::Number(left_val / right_val));
             }
             TokenType::Star => {
-                return Ok(Some(Box::new(
-                    left.unwrap().downcast_ref::<f64>().unwrap() * right.unwrap().downcast_ref::<f64>().unwrap(),
-                )))
+                let (left_val, right_val) = check_number_operands(binary, &left, &right)?;
+                return Ok(Value::Number(left_val * right_val));
             }
-            TokenType::Plus => {
-                let left_val = left.unwrap();
-                let right_val = right.unwrap();
-                let left_type = (&*left_val).type_id();
-                let right_type = (&*right_val).type_id();
-
-                if (left_type == TypeId::of::<f64>())
-                    && (right_type == TypeId::of::<f64>())
-                {
-                    return Ok(Some(Box::new(
-                        left_val.downcast_ref::<f64>().unwrap() + left_val.downcast_ref::<f64>().unwrap(),
-                    )));
-                } else if (left_type == TypeId::of::<String>())
-                    && (right_type == TypeId::of::<String>())
-                {
-                    let mut concat_str: String = left_val.downcast_ref::<String>().unwrap().to_owned();
-                    concat_str.push_str(left_val.downcast_ref::<String>().unwrap());
-                    return Ok(Some(Box::new(concat_str)));
+            TokenType::Plus => match (&left, &right) {
+                (Value::Number(left_val), Value::Number(right_val)) => {
+                    return Ok(Value::Number(left_val + right_val));
                 }
-            }
+                (Value::String(left_val), Value::String(right_val)) => {
+                    let mut concat_str: String = left_val.to_owned();
+                    concat_str.push_str(right_val);
+                    return Ok(Value::String(concat_str));
+                }
+                _ => {
+                    return Err(operand_error(
+                        binary,
+                        &left,
+                        &right,
+                        "Operands must be two numbers or two strings.",
+                    ))
+                }
+            },
             _ => (),
         }
 
-        Ok(None)
+        Ok(Value::Nil)
     }
-    fn visit_unary(&mut self, unary: &Unary, context: Option<&()>) -> Result<Option<Box<dyn Any>>, String> {
+    fn visit_unary(&mut self, unary: &Unary, context: Option<&()>) -> Result<Value, RuntimeError> {
         let right = unary.right.accept(self, context)?;
 
         match unary.operator.token_type {
-            TokenType::Minus => return Ok(Some(Box::new(-(right.unwrap().downcast_ref::<f64>().unwrap())))),
-            TokenType::Bang => {
-                let mut truthy = true;
-
-                match right {
-                    Some(val) => {
-                        if let Some(bool_val) = val.downcast_ref::<bool>() {
-                            truthy = bool_val.to_owned();
-                        }
-                    }
-                    None => truthy = false,
-                }
-
-                return Ok(Some(Box::new(truthy)));
+            TokenType::Minus => {
+                return Ok(Value::Number(-check_number_operand(unary, &right)?))
             }
+            TokenType::Bang => return Ok(Value::Bool(!right.is_truthy())),
             _ => (),
         }
 
-        Ok(None)
+        Ok(Value::Nil)
     }
     fn visit_grouping(
         &mut self,
         grouping: &Grouping,
         context: Option<&()>,
-    ) -> Result<Option<Box<dyn Any>>, String> {
+    ) -> Result<Value, RuntimeError> {
         grouping.expression.accept(self, context)
     }
-    fn visit_literal(&mut self, literal: &Literal, _: Option<&()>) -> Result<Option<Box<dyn Any>>, String> {
-        match literal {
-            Literal::Boolean(val) => Ok(Some(Box::new(val.to_owned()))),
-            Literal::Number(val) => Ok(Some(Box::new(val.to_owned()))),
-            Literal::String(val) => Ok(Some(Box::new(val.to_owned()))),
-            Literal::Nil => Ok(None),
+    fn visit_literal(&mut self, literal: &Literal, _: Option<&()>) -> Result<Value, RuntimeError> {
+        match &literal.value {
+            LiteralValue::Boolean(val) => Ok(Value::Bool(*val)),
+            LiteralValue::Number(val) => Ok(Value::Number(*val)),
+            LiteralValue::String(val) => Ok(Value::String(val.to_owned())),
+            LiteralValue::Nil => Ok(Value::Nil),
         }
     }
+    fn visit_variable(&mut self, variable: &Variable, _: Option<&()>) -> Result<Value, RuntimeError> {
+        self.look_up_variable(&variable.name, node_key(variable))
+    }
+    fn visit_assign(&mut self, assign: &Assign, context: Option<&()>) -> Result<Value, RuntimeError> {
+        let value = self.process(&assign.value, context)?;
+        match self.locals.get(&node_key(assign)) {
+            Some(distance) => self
+                .environment
+                .borrow_mut()
+                .assign_at(*distance, &assign.name, value.clone())?,
+            None => self
+                .globals
+                .borrow_mut()
+                .assign(&assign.name, value.clone())?,
+        };
+        Ok(value)
+    }
+    fn visit_logical(&mut self, logical: &Logical, context: Option<&()>) -> Result<Value, RuntimeError> {
+        let left = self.process(&logical.left, context)?;
+
+        // Short-circuit on the left operand and yield it as-is
+        if logical.operator.token_type == TokenType::Or {
+            if left.is_truthy() {
+                return Ok(left);
+            }
+        } else if !left.is_truthy() {
+            return Ok(left);
+        }
+
+        self.process(&logical.right, context)
+    }
+    fn visit_call(&mut self, call: &Call, context: Option<&()>) -> Result<Value, RuntimeError> {
+        let callee = self.process(&call.callee, context)?;
+
+        let mut arguments: Vec<Value> = Vec::new();
+        for argument in &call.arguments {
+            arguments.push(self.process(argument, context)?);
+        }
+
+        // Fail before runaway recursion exhausts the native stack
+        if self.call_depth >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::new(&call.paren, String::from("Stack overflow.")));
+        }
+
+        self.call_depth += 1;
+        let result = match &callee {
+            Value::Callable(function) => check_arity(&call.paren, function.arity(), arguments.len())
+                .and_then(|()| function.call(self, arguments)),
+            // Instances keep the very class value they were created from
+            Value::Class(class) => check_arity(&call.paren, class.arity(), arguments.len())
+                .and_then(|()| LoxClass::instantiate(class, self, arguments)),
+            _ => Err(RuntimeError::new(
+                &call.paren,
+                String::from("Can only call functions and classes."),
+            )
+            .with_label(call.callee.span(), format!("this is {}", callee.type_name()))),
+        };
+        self.call_depth -= 1;
+        result
+    }
+    fn visit_get(&mut self, get: &Get, context: Option<&()>) -> Result<Value, RuntimeError> {
+        let object = self.process(&get.object, context)?;
+        match object {
+            Value::Instance(instance) => LoxInstance::get(&instance, &get.name),
+            _ => Err(RuntimeError::new(&get.name, String::from("Only instances have properties."))),
+        }
+    }
+    fn visit_set(&mut self, set: &Set, context: Option<&()>) -> Result<Value, RuntimeError> {
+        let object = self.process(&set.object, context)?;
+        let instance = match object {
+            Value::Instance(instance) => instance,
+            _ => return Err(RuntimeError::new(&set.name, String::from("Only instances have fields."))),
+        };
+
+        let value = self.process(&set.value, context)?;
+        instance.borrow_mut().set(&set.name, value.clone());
+        Ok(value)
+    }
+    fn visit_this(&mut self, this: &This, _: Option<&()>) -> Result<Value, RuntimeError> {
+        self.look_up_variable(&this.keyword, node_key(this))
+    }
+    fn visit_super(&mut self, sup: &Super, _: Option<&()>) -> Result<Value, RuntimeError> {
+        let distance = match self.locals.get(&node_key(sup)) {
+            Some(distance) => *distance,
+            None => {
+                return Err(RuntimeError::new(&sup.keyword, String::from("Undefined variable 'super'.")))
+            }
+        };
+
+        let superclass = self.environment.borrow().get_at(distance, &sup.keyword)?;
+        // `this` always lives in the scope just inside the one binding `super`
+        let this_keyword = Token {
+            token_type: TokenType::This,
+            lexeme: Some(String::from("this")),
+            span: sup.keyword.span,
+        };
+        let object = self.environment.borrow().get_at(distance - 1, &this_keyword)?;
+
+        let method = match superclass {
+            Value::Class(class) => class.find_method(sup.method.lexeme.as_deref().unwrap_or("_")),
+            _ => None,
+        };
+
+        match (method, object) {
+            (Some(method), Value::Instance(instance)) => {
+                Ok(Value::Callable(Rc::new(method.bind(instance))))
+            }
+            _ => Err(RuntimeError::new(
+                &sup.method,
+                format!("Undefined property '{}'.", sup.method.lexeme.as_deref().unwrap_or("_")),
+            )),
+        }
+    }
+}
+
+impl StmtVisitor<Result<(), Unwind>, ()> for Interpreter {
+    fn process_stmt(&mut self, stmt: &Stmt, context: Option<&()>) -> Result<(), Unwind> {
+        stmt.accept(self, context)
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression, context: Option<&()>) -> Result<(), Unwind> {
+        self.process(&stmt.expression, context)?;
+        Ok(())
+    }
+    fn visit_print_stmt(&mut self, stmt: &Print, context: Option<&()>) -> Result<(), Unwind> {
+        let value = self.process(&stmt.expression, context)?;
+        // A closed output stream shouldn't abort the program
+        let _ = writeln!(self.output, "{}", value);
+        Ok(())
+    }
+    fn visit_var_stmt(&mut self, stmt: &Var, context: Option<&()>) -> Result<(), Unwind> {
+        let value = match &stmt.initializer {
+            Some(initializer) => self.process(initializer, context)?,
+            None => Value::Nil,
+        };
+
+        let name = stmt.name.lexeme.clone().unwrap_or_default();
+        self.environment.borrow_mut().define(name, value);
+        Ok(())
+    }
+    fn visit_block_stmt(&mut self, stmt: &Block, _: Option<&()>) -> Result<(), Unwind> {
+        let environment = Environment::new(Some(Rc::clone(&self.environment)));
+        self.execute_block(&stmt.statements, Rc::new(RefCell::new(environment)))
+    }
+    fn visit_if_stmt(&mut self, stmt: &If, context: Option<&()>) -> Result<(), Unwind> {
+        let condition = self.process(&stmt.condition, context)?;
+        if condition.is_truthy() {
+            self.process_stmt(&stmt.then_branch, context)?;
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.process_stmt(else_branch, context)?;
+        }
+        Ok(())
+    }
+    fn visit_while_stmt(&mut self, stmt: &While, context: Option<&()>) -> Result<(), Unwind> {
+        while self.process(&stmt.condition, context)?.is_truthy() {
+            self.process_stmt(&stmt.body, context)?;
+        }
+        Ok(())
+    }
+    fn visit_function_stmt(&mut self, stmt: &Function, _: Option<&()>) -> Result<(), Unwind> {
+        let function = LoxFunction::new(stmt.clone(), Rc::clone(&self.environment), false, self.source);
+        let name = stmt.name.lexeme.clone().unwrap_or_default();
+        self.environment
+            .borrow_mut()
+            .define(name, Value::Callable(Rc::new(function)));
+        Ok(())
+    }
+    fn visit_return_stmt(&mut self, stmt: &Return, context: Option<&()>) -> Result<(), Unwind> {
+        let value = match &stmt.value {
+            Some(value) => self.process(value, context)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value))
+    }
+    fn visit_class_stmt(&mut self, stmt: &Class, _: Option<&()>) -> Result<(), Unwind> {
+        let mut superclass: Option<Rc<LoxClass>> = None;
+        if let Some(variable) = &stmt.superclass {
+            match self.look_up_variable(&variable.name, node_key(variable))? {
+                Value::Class(class) => superclass = Some(class),
+                _ => {
+                    return Err(Unwind::Error(RuntimeError::new(
+                        &variable.name,
+                        String::from("Superclass must be a class."),
+                    )))
+                }
+            }
+        }
+
+        let name = stmt.name.lexeme.clone().unwrap_or_default();
+        self.environment.borrow_mut().define(name.clone(), Value::Nil);
+
+        // Methods of a subclass close over an extra scope that binds `super`
+        let enclosing = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::new(Some(Rc::clone(&self.environment)));
+            environment.define(String::from("super"), Value::Class(Rc::clone(superclass)));
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+
+        let mut methods: HashMap<String, Rc<LoxFunction>> = HashMap::new();
+        for method in &stmt.methods {
+            let method_name = method.name.lexeme.clone().unwrap_or_default();
+            let function = LoxFunction::new(
+                method.clone(),
+                Rc::clone(&self.environment),
+                method_name == "init",
+                self.source,
+            );
+            methods.insert(method_name, Rc::new(function));
+        }
+
+        let class = LoxClass::new(name, superclass, methods);
+        self.environment = enclosing;
+        self.environment
+            .borrow_mut()
+            .assign(&stmt.name, Value::Class(Rc::new(class)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+    use std::thread;
+
+    use super::{Interpreter, RuntimeError};
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::scanner::Scanner;
+
+    // Collects printed output where the test can still read it
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs `source` and returns everything it printed.
+    pub(crate) fn run(source: &str) -> String {
+        try_run(source).unwrap_or_else(|message| panic!("runtime error: {}", message))
+    }
+
+    /// Runs `source`, returning the message of the runtime error it stops with, if any.
+    pub(crate) fn try_run(source: &str) -> Result<String, String> {
+        let output = Output::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+        execute(&mut interpreter, source).map_err(|runtime_err| runtime_err.message)?;
+
+        let printed = output.0.borrow().clone();
+        Ok(String::from_utf8(printed).unwrap())
+    }
+
+    // Scans, parses, resolves and runs `source` on an existing interpreter
+    fn execute(interpreter: &mut Interpreter, source: &str) -> Result<(), RuntimeError> {
+        let scanner = Scanner {
+            source: source.to_owned(),
+        };
+        let (tokens, scan_errs) = scanner.scan_tokens();
+        assert!(scan_errs.is_empty(), "scan error: {}", scan_errs[0].message);
+        let statements = match Parser::new(tokens).parse() {
+            Ok(statements) => statements,
+            Err(parser_errs) => panic!("parse error: {}", parser_errs[0].message),
+        };
+
+        let mut resolver = Resolver::new(interpreter);
+        resolver.resolve(&statements);
+        if let Some(resolve_err) = resolver.errors.first() {
+            panic!("resolve error: {}", resolve_err.message);
+        }
+        interpreter.add_source(source, None);
+        interpreter.interpret(&statements)
+    }
+
+    #[test]
+    fn closures_keep_their_environment() {
+        let source = "
+            fun makeCounter() {
+                var i = 0;
+                fun count() {
+                    i = i + 1;
+                    print i;
+                }
+                return count;
+            }
+
+            var counter = makeCounter();
+            counter();
+            counter();
+        ";
+        assert_eq!(run(source), "1\n2\n");
+    }
+
+    #[test]
+    fn errors_keep_the_input_their_function_came_from() {
+        let mut interpreter = Interpreter::with_output(Box::new(Output::default()));
+        assert!(execute(&mut interpreter, "fun f() { return nil - 1; }").is_ok());
+        let runtime_err = execute(&mut interpreter, "var value = f();").err().unwrap();
+        assert_eq!(runtime_err.source, Some(0));
+        assert_eq!(interpreter.source(0).unwrap().text, "fun f() { return nil - 1; }");
+
+        let runtime_err = execute(&mut interpreter, "print nil - 1;").err().unwrap();
+        assert_eq!(runtime_err.source, None);
+    }
+
+    #[test]
+    fn equality_follows_the_reference() {
+        let source = r#"
+            print nil == nil;
+            print nil == false;
+            print 1 == 1;
+            print 1 == "1";
+            print "a" == "a";
+            print "a" != "b";
+            print true != 1;
+        "#;
+        assert_eq!(run(source), "true\nfalse\ntrue\nfalse\ntrue\ntrue\ntrue\n");
+    }
+
+    #[test]
+    fn instances_are_equal_only_to_themselves() {
+        let source = "
+            class A {}
+            var a = A();
+            var b = a;
+            print a == b;
+            print a == A();
+        ";
+        assert_eq!(run(source), "true\nfalse\n");
+    }
+
+    #[test]
+    fn only_nil_and_false_are_falsey() {
+        let source = r#"
+            print !nil;
+            print !false;
+            print !true;
+            print !0;
+            print !"";
+            print !!nil;
+        "#;
+        assert_eq!(run(source), "true\ntrue\nfalse\nfalse\nfalse\nfalse\n");
+    }
+
+    #[test]
+    fn plus_adds_numbers_and_concatenates_strings() {
+        let source = r#"
+            print 1 + 2;
+            print 0.5 + 0.25;
+            print "con" + "cat";
+            print "" + "";
+        "#;
+        assert_eq!(run(source), "3\n0.75\nconcat\n\n");
+    }
+
+    #[test]
+    fn plus_rejects_mixed_operands() {
+        let message = String::from("Operands must be two numbers or two strings.");
+        assert_eq!(try_run(r#"print 1 + "1";"#), Err(message.clone()));
+        assert_eq!(try_run(r#"print "1" + nil;"#), Err(message));
+        assert_eq!(try_run(r#"print 1 - "1";"#), Err(String::from("Operands must be numbers.")));
+    }
+
+    #[test]
+    fn runaway_recursion_is_a_runtime_error() {
+        let source = "
+            fun f(n) {
+                if (n == 0) return 0;
+                return 1 + f(n - 1);
+            }
+            print f(500);
+            print f(1000);
+        ";
+        // Test threads are too small to reach the limit, the CLI uses the same size
+        let runner = thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(move || try_run(source))
+            .unwrap();
+        assert_eq!(runner.join().unwrap(), Err(String::from("Stack overflow.")));
+    }
 }
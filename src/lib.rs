@@ -0,0 +1,17 @@
+//! A tree-walking interpreter for Lox, usable from other Rust programs.
+//!
+//! Source goes through `Scanner`, `Parser` and `Resolver` before an `Interpreter` runs it;
+//! values cross the boundary as `Value`.
+
+pub mod ast;
+pub mod callable;
+pub mod class;
+pub mod diagnostic;
+pub mod environment;
+pub mod formatter;
+pub mod highlight;
+pub mod interpreter;
+pub mod parser;
+pub mod resolver;
+pub mod scanner;
+pub mod value;
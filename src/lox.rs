@@ -1,87 +1,338 @@
-mod ast;
-mod parser;
-mod scanner;
-mod interpreter;
+mod repl;
 
 use std::env;
 use std::fs;
 use std::io;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::process;
+use std::thread;
 
-use ast::tree::AstPrinter;
-use ast::tree::AstVisitor;
-use parser::Parser;
-use scanner::scanner::Scanner;
+use lox_rs::ast::tree::{AstPrinter, Stmt, StmtVisitor};
+use lox_rs::diagnostic::{Diagnostic, ErrorFormat, Renderer};
+use lox_rs::formatter::format_tokens;
+use lox_rs::highlight::{paint_value, use_color};
+use lox_rs::interpreter::Interpreter;
+use lox_rs::parser::Parser;
+use lox_rs::resolver::Resolver;
+use lox_rs::scanner::scanner::Scanner;
 
-fn main() {
-    // Get list of arguments
-    let args: Vec<String> = env::args().collect();
-    // The first arg is always the app name
-    if args.len() > 2 {
-        println!("Usage: rlox [script]");
-        process::exit(64);
-    } else if args.len() == 2 {
-        // Arg is a path
-        run_file(&args[1]);
-    } else {
-        // No arg => Run interactive mod
-        run_prompt();
+// Exit codes from sysexits.h, as used by the reference implementation
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_NOINPUT: i32 = 66;
+const EX_SOFTWARE: i32 = 70;
+const EX_IOERR: i32 = 74;
+
+// Scripts run on a thread with this much stack, so deep recursion stops at
+// `MAX_CALL_DEPTH` with a runtime error instead of overflowing the native stack
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+const HELP: &str = "\
+Usage: lox [options] [command] [input]
+
+Commands:
+  run <input> [args...]  Run a script; the default when a file is given
+  repl                   Start the interactive prompt; the default with no input
+  check <input>          Scan, parse and resolve without running
+  tokens <input>         Print the tokens of the script
+  ast <input>            Print the syntax tree of the script
+  fmt <input>            Print the script in canonical layout
+
+Input is a path, `-` to read standard input, or `-e <code>` for inline code.
+Everything after a script path or `-` to run is passed to the script, though
+Lox has no way to read it yet. Other commands and `-e` take options after the input.
+
+Options:
+  -e <code>                    Use <code> as the script
+  --error-format=human|json    How to write diagnostics (default: human)
+  --ast                        Same as the `ast` command
+  -h, --help                   Show this message
+
+Exit codes: 64 bad usage, 65 invalid script, 66 unreadable input, 70 runtime error.
+";
+
+/// What to do with the input.
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Repl,
+    Run,
+    Check,
+    Tokens,
+    Ast,
+    Fmt,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Command> {
+        match name {
+            "repl" => Some(Command::Repl),
+            "run" => Some(Command::Run),
+            "check" => Some(Command::Check),
+            "tokens" => Some(Command::Tokens),
+            "ast" => Some(Command::Ast),
+            "fmt" => Some(Command::Fmt),
+            _ => None,
+        }
     }
 }
 
-fn run_file(path: &String) {
-    let source = fs::read_to_string(path).expect("File should be able to read");
-    if run(&source).is_err() {
-        process::exit(65);
-    };
+/// Where the script comes from.
+enum Input {
+    File(String),
+    Stdin,
+    Inline(String),
 }
 
-fn run_prompt() {
-    let mut buf = String::new();
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    loop {
-        print!("> ");
-        stdout.flush().unwrap();
-        stdin.read_line(&mut buf).expect("Failed to read line");
-        let line = buf.to_string();
-        buf.clear();
-
-        if run(&line).is_err() {
-            process::exit(65);
-        };
+/// Command line flags that change how source is run.
+struct Options {
+    command: Command,
+    error_format: ErrorFormat,
+}
+
+fn main() {
+    let runner = thread::Builder::new().stack_size(STACK_SIZE).spawn(start);
+    match runner.map(|runner| runner.join()) {
+        Ok(Ok(())) => (),
+        // The panic message has already been printed
+        Ok(Err(_)) => process::exit(EX_SOFTWARE),
+        Err(err) => {
+            eprintln!("lox: can't start the interpreter: {}", err);
+            process::exit(EX_SOFTWARE);
+        }
     }
 }
 
-fn run(source: &String) -> Result<String, String> {
-    if source.contains("err") {
-        return Err("Error".to_string());
+fn start() {
+    // Get list of arguments, the first arg is always the app name
+    let mut args = env::args().skip(1);
+    let mut command: Option<Command> = None;
+    let mut input: Option<Input> = None;
+    let mut error_format = ErrorFormat::Human;
+    // Arguments for the script itself, collected but not yet visible to it
+    let mut script_args: Vec<String> = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let next_input = match arg.as_str() {
+            "-h" | "--help" => {
+                print!("{}", HELP);
+                return;
+            }
+            "--error-format=human" => {
+                error_format = ErrorFormat::Human;
+                continue;
+            }
+            "--error-format=json" => {
+                error_format = ErrorFormat::Json;
+                continue;
+            }
+            "--ast" => {
+                match command {
+                    None | Some(Command::Ast) => command = Some(Command::Ast),
+                    Some(_) => usage_error("--ast can't be combined with another command"),
+                }
+                continue;
+            }
+            "-e" => match args.next() {
+                Some(code) => Input::Inline(code),
+                None => usage_error("-e needs the code to run"),
+            },
+            "-" => Input::Stdin,
+            _ if arg.starts_with('-') => usage_error(&format!("unknown option '{}'", arg)),
+            _ if input.is_some() => usage_error(&format!("unexpected argument '{}'", arg)),
+            _ => match Command::from_name(&arg) {
+                Some(name) if command.is_none() && input.is_none() => {
+                    command = Some(name);
+                    continue;
+                }
+                _ => Input::File(arg),
+            },
+        };
+
+        if input.is_some() {
+            usage_error("more than one input given");
+        }
+        input = Some(next_input);
+
+        // Whatever follows a script to run belongs to it, options included
+        if matches!(input, Some(Input::File(_) | Input::Stdin)) && matches!(command, None | Some(Command::Run)) {
+            script_args.extend(args.by_ref());
+        }
     }
 
+    let command = command.unwrap_or(match input {
+        Some(_) => Command::Run,
+        None => Command::Repl,
+    });
+    let options = Options {
+        command,
+        error_format,
+    };
+
+    match (command, input) {
+        (Command::Repl, None) => repl::run_prompt(&options),
+        (Command::Repl, Some(_)) => usage_error("repl takes no input"),
+        (_, None) => usage_error("no input given"),
+        (_, Some(input)) => run_input(input, &options),
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("lox: {}", message);
+    eprintln!("Try 'lox --help' for more information.");
+    process::exit(EX_USAGE);
+}
+
+fn run_input(input: Input, options: &Options) {
+    let (source, file) = match input {
+        Input::File(path) => match fs::read_to_string(&path) {
+            Ok(source) => (source, Some(path)),
+            Err(err) => {
+                eprintln!("lox: can't open '{}': {}", path, err);
+                process::exit(EX_NOINPUT);
+            }
+        },
+        Input::Stdin => {
+            let mut source = String::new();
+            if let Err(err) = io::stdin().read_to_string(&mut source) {
+                eprintln!("lox: can't read standard input: {}", err);
+                process::exit(EX_IOERR);
+            }
+            (source, Some(String::from("<stdin>")))
+        }
+        Input::Inline(code) => (code, None),
+    };
+
+    let mut interpreter = Interpreter::new();
+    if let Err(code) = run(&source, file.as_deref(), options, &mut interpreter, false) {
+        process::exit(code);
+    };
+}
+
+/// Runs `source` on `interpreter`, returning the process exit code on failure.
+/// At the prompt a lone expression may leave off its ';' and has its value printed.
+fn run(
+    source: &str,
+    file: Option<&str>,
+    options: &Options,
+    interpreter: &mut Interpreter,
+    prompt: bool,
+) -> Result<(), i32> {
     let scanner = Scanner {
         source: source.to_owned(),
     };
 
-    match scanner.scan_tokens() {
-        Ok(tokens) => {
-            let mut parser: Parser = Parser::new(tokens);
-            if let Ok(expr) = parser.parse() {
-                let mut visitor = AstPrinter;
-                let val = visitor.process(&expr, None);
-                print!("\n{}\n", val)
+    let renderer = Renderer::new(source, file, options.error_format);
+
+    let (tokens, scan_errs) = scanner.scan_tokens();
+    for scan_err in &scan_errs {
+        report(&renderer, Diagnostic::from(scan_err));
+    }
+
+    if options.command == Command::Tokens {
+        dump(|out| {
+            for token in &tokens {
+                writeln!(out, "{}:{} {}", token.span.line, token.span.column, token)?;
+            }
+            Ok(())
+        })?;
+        return if scan_errs.is_empty() { Ok(()) } else { Err(EX_DATAERR) };
+    }
+
+    // The formatter lays out the tokens, comments included; parsing only vets them
+    let layout = (options.command == Command::Fmt).then(|| tokens.clone());
+
+    // Parse even if scanning failed, so every syntax error is reported at once
+    let mut parser: Parser = Parser::new(tokens);
+    let parsed = if prompt { parser.parse_prompt() } else { parser.parse() };
+    let statements = match parsed {
+        Ok(statements) => statements,
+        Err(parser_errs) => {
+            for parser_err in &parser_errs {
+                report(&renderer, Diagnostic::from(parser_err));
+            }
+            return Err(EX_DATAERR);
+        }
+    };
+    if !scan_errs.is_empty() {
+        return Err(EX_DATAERR);
+    }
+
+    match options.command {
+        Command::Ast => {
+            let mut printer = AstPrinter;
+            return dump(|out| {
+                for statement in &statements {
+                    writeln!(out, "{}", printer.process_stmt(statement, None))?;
+                }
+                Ok(())
+            });
+        }
+        Command::Fmt => {
+            let formatted = format_tokens(layout.as_deref().unwrap_or_default());
+            return dump(|out| out.write_all(formatted.as_bytes()));
+        }
+        _ => (),
+    }
+
+    let mut resolver = Resolver::new(interpreter);
+    resolver.resolve(&statements);
+    if !resolver.errors.is_empty() {
+        for resolve_err in &resolver.errors {
+            let diagnostic = Diagnostic {
+                code: "resolve-error",
+                ..Diagnostic::from(resolve_err)
             };
+            report(&renderer, diagnostic);
         }
-        Err(scan_err) => error(scan_err.line, scan_err.message),
+        return Err(EX_DATAERR);
+    }
+    if options.command == Command::Check {
+        return Ok(());
+    }
+
+    let source_id = interpreter.add_source(source, file);
+    let result = match statements.as_slice() {
+        [Stmt::Expression(stmt)] if prompt => interpreter
+            .evaluate(&stmt.expression)
+            .map(|value| {
+                if use_color(&io::stdout()) {
+                    println!("{}", paint_value(&value))
+                } else {
+                    println!("{}", value)
+                }
+            }),
+        _ => interpreter.interpret(&statements),
     };
+    if let Err(runtime_err) = result {
+        // A function from an earlier input is shown against the text it was declared in
+        let diagnostic = Diagnostic::from(&runtime_err);
+        match runtime_err.source.filter(|id| *id != source_id).and_then(|id| interpreter.source(id)) {
+            Some(origin) => report(
+                &Renderer::new(&origin.text, origin.file.as_deref(), options.error_format),
+                diagnostic,
+            ),
+            None => report(&renderer, diagnostic),
+        }
+        return Err(EX_SOFTWARE);
+    }
 
-    Ok("Success".to_string())
+    Ok(())
 }
 
-fn error(line: usize, message: String) {
-    report(line, &String::from(""), message);
+/// Writes the output of `tokens`, `ast` or `fmt` to stdout.
+/// A reader that stops early, like `head`, just ends the dump.
+fn dump(write: impl FnOnce(&mut io::StdoutLock) -> io::Result<()>) -> Result<(), i32> {
+    let mut out = io::stdout().lock();
+    match write(&mut out).and_then(|()| out.flush()) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        Err(err) => {
+            eprintln!("lox: can't write standard output: {}", err);
+            Err(EX_IOERR)
+        }
+    }
 }
 
-fn report(line: usize, where_err: &String, message: String) {
-    println!("[line {}] Error{}: {}", line, where_err, message)
+fn report(renderer: &Renderer, diagnostic: Diagnostic) {
+    renderer.emit(&diagnostic)
 }
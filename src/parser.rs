@@ -1,33 +1,53 @@
 use crate::ast::tree::*;
+use crate::scanner::token::Span;
 use crate::scanner::token::Token;
 use crate::scanner::token::TokenType;
 
+const MAX_ARGUMENTS: usize = 255;
+
 pub struct ParserErr {
-    line: usize,
-    where_err: String,
-    message: String,
+    pub span: Span,
+    pub where_err: String,
+    pub message: String,
+    pub notes: Vec<String>,
+    // The input ran out, so more of it might fix the error
+    pub at_eof: bool,
 }
 impl ParserErr {
     pub fn new(token: &Token, message: String) -> Self {
         Self {
-            line: token.line,
+            span: token.span,
             where_err: match token.token_type {
                 TokenType::Eof => String::from(" at end"),
                 _ => format!(" at '{}'", token.lexeme.as_deref().unwrap_or("_")),
             },
-            message: message
+            message,
+            notes: Vec::new(),
+            at_eof: token.token_type == TokenType::Eof,
         }
     }
+
+    /// Adds a hint shown below the error.
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(String::from(note));
+        self
+    }
 }
 pub struct Parser {
     tokens: Vec<Token>,
     pos: usize,
+    errors: Vec<ParserErr>,
 }
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
         Self {
-            tokens: tokens,
+            // Comments carry no meaning for the grammar. Bad lexemes stay, so they can stand in for an expression
+            tokens: tokens
+                .into_iter()
+                .filter(|token| !matches!(token.token_type, TokenType::Comment(_)))
+                .collect(),
             pos: 0,
+            errors: Vec::new(),
         }
     }
 
@@ -36,7 +56,9 @@ impl Parser {
     }
 
     fn advance(&mut self) -> &Token {
-        self.pos += 1;
+        if !self.is_eof() {
+            self.pos += 1;
+        }
         self.previous()
     }
 
@@ -52,12 +74,351 @@ impl Parser {
         &self.tokens[self.pos - 1]
     }
 
-    pub fn parse(&mut self) -> Result<Box<Expr>, ParserErr> {
-        self.expression()
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, ParserErr> {
+        if self.check(token_type) {
+            return Ok(self.advance());
+        }
+        Err(ParserErr::new(self.peek(), String::from(message)))
+    }
+
+    fn consume_identifier(&mut self, message: &str) -> Result<Token, ParserErr> {
+        if let TokenType::Identifier(_) = self.peek().token_type {
+            return Ok(self.advance().clone());
+        }
+        Err(ParserErr::new(self.peek(), String::from(message)))
+    }
+
+    /// Parses the whole program, collecting every error instead of stopping at the first.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParserErr>> {
+        let mut statements: Vec<Stmt> = Vec::new();
+        while !self.is_eof() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Parses prompt input, which may also be a single expression without its ';'.
+    pub fn parse_prompt(&mut self) -> Result<Vec<Stmt>, Vec<ParserErr>> {
+        let errors = match self.parse() {
+            Ok(statements) => return Ok(statements),
+            Err(errors) => errors,
+        };
+
+        // Report the errors from the statement grammar if it isn't a bare expression either
+        self.pos = 0;
+        match self.expression() {
+            Ok(expr) if self.is_eof() && self.errors.is_empty() => {
+                Ok(vec![Stmt::Expression(Expression::new(expr))])
+            }
+            _ => Err(errors),
+        }
+    }
+
+    /// Discards tokens until the start of what is likely the next statement.
+    fn synchronize(&mut self) {
+        self.advance();
+
+        while !self.is_eof() {
+            if self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+
+            match self.peek().token_type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    fn declaration(&mut self) -> Option<Stmt> {
+        let start = self.pos;
+        match self.try_declaration() {
+            Ok(statement) => Some(statement),
+            Err(parser_err) => {
+                // The scanner already reported the bad lexeme, anything it broke afterwards is noise
+                let after_bad_lexeme = self.tokens[start..=self.pos]
+                    .iter()
+                    .any(|token| token.token_type == TokenType::Error);
+                if !after_bad_lexeme {
+                    self.errors.push(parser_err);
+                }
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn try_declaration(&mut self) -> Result<Stmt, ParserErr> {
+        if self.check(TokenType::Class) {
+            self.advance();
+            return self.class_declaration();
+        }
+        if self.check(TokenType::Fun) {
+            self.advance();
+            return Ok(Stmt::Function(self.function("function")?));
+        }
+        if self.check(TokenType::Var) {
+            self.advance();
+            return self.var_declaration();
+        }
+        self.statement()
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ParserErr> {
+        let name = self.consume_identifier("Expect class name.")?;
+
+        let mut superclass = None;
+        if self.check(TokenType::Less) {
+            self.advance();
+            let superclass_name = self.consume_identifier("Expect superclass name.")?;
+            superclass = Some(Variable::new(superclass_name));
+        }
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods: Vec<Function> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_eof() {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::Class(Class::new(name, superclass, methods)))
+    }
+
+    fn function(&mut self, kind: &str) -> Result<Function, ParserErr> {
+        let name = self.consume_identifier(&format!("Expect {} name.", kind))?;
+        self.consume(TokenType::LeftParen, &format!("Expect '(' after {} name.", kind))?;
+
+        let mut params: Vec<Token> = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let parser_err = ParserErr::new(self.peek(), String::from("Can't have more than 255 parameters."));
+                    self.errors.push(parser_err);
+                }
+                params.push(self.consume_identifier("Expect parameter name.")?);
+
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+        let body = self.block()?;
+        Ok(Function::new(name, params, body))
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, ParserErr> {
+        let name = self.consume_identifier("Expect variable name.")?;
+
+        let mut initializer = None;
+        if self.check(TokenType::Equal) {
+            self.advance();
+            initializer = Some(self.expression()?);
+        }
+
+        self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.")?;
+        Ok(Stmt::Var(Var::new(name, initializer)))
+    }
+
+    fn statement(&mut self) -> Result<Stmt, ParserErr> {
+        if self.check(TokenType::For) {
+            self.advance();
+            return self.for_statement();
+        }
+        if self.check(TokenType::If) {
+            self.advance();
+            return self.if_statement();
+        }
+        if self.check(TokenType::While) {
+            self.advance();
+            return self.while_statement();
+        }
+        if self.check(TokenType::Print) {
+            self.advance();
+            return self.print_statement();
+        }
+        if self.check(TokenType::Return) {
+            self.advance();
+            return self.return_statement();
+        }
+        if self.check(TokenType::LeftBrace) {
+            self.advance();
+            return Ok(Stmt::Block(Block::new(self.block()?)));
+        }
+        self.expression_statement()
+    }
+
+    fn for_statement(&mut self) -> Result<Stmt, ParserErr> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.check(TokenType::Semicolon) {
+            self.advance();
+            None
+        } else if self.check(TokenType::Var) {
+            self.advance();
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(TokenType::Semicolon) {
+            // An omitted condition loops forever; point it at the missing spot
+            Box::new(Expr::Literal(Literal::new(LiteralValue::Boolean(true), self.peek().span)))
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        // Desugar into `{ initializer; while (condition) { body; increment; } }`
+        let mut body = self.statement()?;
+        if let Some(increment) = increment {
+            body = Stmt::Block(Block::new(vec![
+                body,
+                Stmt::Expression(Expression::new(increment)),
+            ]));
+        }
+        body = Stmt::While(While::new(condition, Box::new(body)));
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(Block::new(vec![initializer, body]));
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, ParserErr> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        // A dangling else binds to the nearest if
+        let mut else_branch = None;
+        if self.check(TokenType::Else) {
+            self.advance();
+            else_branch = Some(Box::new(self.statement()?));
+        }
+
+        Ok(Stmt::If(If::new(condition, then_branch, else_branch)))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, ParserErr> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = self.statement()?;
+
+        Ok(Stmt::While(While::new(condition, Box::new(body))))
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, ParserErr> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print(Print::new(value)))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParserErr> {
+        let keyword = self.previous().clone();
+
+        let mut value = None;
+        if !self.check(TokenType::Semicolon) {
+            value = Some(self.expression()?);
+        }
+
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return(Return::new(keyword, value)))
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt, ParserErr> {
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression(Expression::new(expr)))
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, ParserErr> {
+        let mut statements: Vec<Stmt> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_eof() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
     }
 
     fn expression(&mut self) -> Result<Box<Expr>, ParserErr> {
-        self.equality()
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> Result<Box<Expr>, ParserErr> {
+        let expr = self.or()?;
+
+        if self.check(TokenType::Equal) {
+            let equals = self.advance().clone();
+            // Assignment is right-associative, so recurse instead of looping
+            let value = self.assignment()?;
+
+            return match *expr {
+                Expr::Variable(variable) => Ok(Box::new(Expr::Assign(Assign::new(variable.name, value)))),
+                Expr::Get(get) => Ok(Box::new(Expr::Set(Set::new(get.object, get.name, value)))),
+                target => {
+                    // No need to synchronize, the parser still knows where it is
+                    self.errors.push(ParserErr::new(&equals, String::from("Invalid assignment target.")));
+                    Ok(Box::new(target))
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Box<Expr>, ParserErr> {
+        let mut expr = self.and()?;
+
+        while self.check(TokenType::Or) {
+            let operator = self.advance().clone();
+            expr = Box::new(Expr::Logical(Logical::new(expr, operator, self.and()?)));
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Box<Expr>, ParserErr> {
+        let mut expr = self.equality()?;
+
+        while self.check(TokenType::And) {
+            let operator = self.advance().clone();
+            expr = Box::new(Expr::Logical(Logical::new(expr, operator, self.equality()?)));
+        }
+
+        Ok(expr)
     }
 
     fn equality(&mut self) -> Result<Box<Expr>, ParserErr> {
@@ -71,13 +432,13 @@ impl Parser {
                 Token {
                     token_type: operator.token_type.clone(),
                     lexeme: operator.lexeme.clone(),
-                    line: operator.line,
+                    span: operator.span,
                 },
                 self.comparison()?,
             )));
         }
 
-        return Ok(expr);
+        Ok(expr)
     }
 
     fn comparison(&mut self) -> Result<Box<Expr>, ParserErr> {
@@ -95,12 +456,12 @@ impl Parser {
                 Token {
                     token_type: operator.token_type.clone(),
                     lexeme: operator.lexeme.clone(),
-                    line: operator.line,
+                    span: operator.span,
                 },
                 self.term()?,
             )));
         }
-        return Ok(expr);
+        Ok(expr)
     }
 
     fn term(&mut self) -> Result<Box<Expr>, ParserErr> {
@@ -113,12 +474,12 @@ impl Parser {
                 Token {
                     token_type: operator.token_type.clone(),
                     lexeme: operator.lexeme.clone(),
-                    line: operator.line,
+                    span: operator.span,
                 },
                 self.factor()?,
             )));
         }
-        return Ok(expr);
+        Ok(expr)
     }
     fn factor(&mut self) -> Result<Box<Expr>, ParserErr> {
         
@@ -131,12 +492,12 @@ impl Parser {
                 Token {
                     token_type: operator.token_type.clone(),
                     lexeme: operator.lexeme.clone(),
-                    line: operator.line,
+                    span: operator.span,
                 },
                 self.unary()?,
             )));
         }
-        return Ok(expr);
+        Ok(expr)
     }
     fn unary(&mut self) -> Result<Box<Expr>, ParserErr> {
         if self.check(TokenType::Bang) || self.check(TokenType::Minus) {
@@ -146,31 +507,80 @@ impl Parser {
                 Token {
                     token_type: operator.token_type.clone(),
                     lexeme: operator.lexeme.clone(),
-                    line: operator.line,
+                    span: operator.span,
                 },
                 self.unary()?,
             ))));
         }
-        return self.primary();
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Box<Expr>, ParserErr> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.check(TokenType::LeftParen) {
+                self.advance();
+                expr = self.finish_call(expr)?;
+            } else if self.check(TokenType::Dot) {
+                self.advance();
+                let name = self.consume_identifier("Expect property name after '.'.")?;
+                expr = Box::new(Expr::Get(Get::new(expr, name)));
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Box<Expr>) -> Result<Box<Expr>, ParserErr> {
+        let mut arguments: Vec<Expr> = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let parser_err = ParserErr::new(self.peek(), String::from("Can't have more than 255 arguments."));
+                    self.errors.push(parser_err);
+                }
+                arguments.push(*self.expression()?);
+
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?.clone();
+        Ok(Box::new(Expr::Call(Call::new(callee, paren, arguments))))
     }
 
     fn primary(&mut self) -> Result<Box<Expr>, ParserErr> {
+        let span = self.peek().span;
         let result = match self.peek().token_type {
-            TokenType::False => Some(Expr::Literal(Literal::Boolean(false))),
-            TokenType::True => Some(Expr::Literal(Literal::Boolean(true))),
-            TokenType::Nil => Some(Expr::Literal(Literal::Nil)),
-            TokenType::Number(val) => Some(Expr::Literal(Literal::Number(val))),
-            TokenType::StringLiteral(ref val) => Some(Expr::Literal(Literal::String(val.to_string()))),
+            TokenType::False => Some(Expr::Literal(Literal::new(LiteralValue::Boolean(false), span))),
+            TokenType::True => Some(Expr::Literal(Literal::new(LiteralValue::Boolean(true), span))),
+            TokenType::Nil => Some(Expr::Literal(Literal::new(LiteralValue::Nil, span))),
+            TokenType::Number(val) => Some(Expr::Literal(Literal::new(LiteralValue::Number(val), span))),
+            TokenType::StringLiteral(ref val) => {
+                Some(Expr::Literal(Literal::new(LiteralValue::String(val.to_string()), span)))
+            }
+            // Already reported by the scanner, so parse on as if it were a value
+            TokenType::Error => Some(Expr::Literal(Literal::new(LiteralValue::Nil, span))),
+            TokenType::This => Some(Expr::This(This::new(self.peek().clone()))),
+            TokenType::Identifier(_) => Some(Expr::Variable(Variable::new(self.peek().clone()))),
+            TokenType::Super => {
+                let keyword = self.advance().clone();
+                self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+                let method = self.consume_identifier("Expect superclass method name.")?;
+                return Ok(Box::new(Expr::Super(Super::new(keyword, method))));
+            }
             TokenType::LeftParen => {
                 self.advance();
                 let expr = self.expression()?;
-                if self.check(TokenType::RightParen) {
-                    self.advance();
-                    Ok(())
-                } else {
-                    Err(ParserErr::new(self.peek(), String::from("Expect expression.")))
-                }?;
-                return Ok(Box::new(Expr::Grouping(Grouping::new(expr))));
+                let paren = self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+                let span = span.to(&paren.span);
+                return Ok(Box::new(Expr::Grouping(Grouping::new(expr, span))));
             }
             _ => None
         };
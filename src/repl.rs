@@ -0,0 +1,226 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use lox_rs::environment::Environment;
+use lox_rs::highlight::{highlight_source, use_color};
+use lox_rs::interpreter::Interpreter;
+use lox_rs::parser::Parser;
+use lox_rs::scanner::scanner::{Scanner, KEYWORDS};
+use lox_rs::scanner::token::TokenType;
+
+use crate::{run, Command, Options};
+
+const HISTORY_FILE: &str = ".lox_history";
+
+const HELP: &str = "\
+:tokens <code>  print the tokens scanned from <code>
+:ast <code>     print the syntax tree parsed from <code>
+:env            list the global variables
+:load <file>    run a script in this session
+:reset          forget every definition
+:time <code>    run <code> and report how long it took
+:help           show this message";
+
+/// Reads and runs lines until EOF, keeping globals alive between them.
+/// Errors are reported and the session carries on.
+pub fn run_prompt(options: &Options) {
+    let mut interpreter = Interpreter::new();
+    let mut editor: Editor<LoxHelper, DefaultHistory> = Editor::new().expect("Failed to set up the terminal");
+    editor.set_helper(Some(LoxHelper {
+        globals: interpreter.globals(),
+    }));
+
+    let history = history_path();
+    if let Some(path) = &history {
+        // There is no history yet on the first run
+        let _ = editor.load_history(path);
+    }
+
+    // Input collected so far, spanning several lines while it is incomplete
+    let mut source = String::new();
+    loop {
+        // `:reset` swaps the interpreter, so keep completing from the live globals
+        if let Some(helper) = editor.helper_mut() {
+            helper.globals = interpreter.globals();
+        }
+
+        let prompt = if source.is_empty() { "> " } else { "... " };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            // Ctrl-C drops the pending input, Ctrl-D ends the session
+            Err(ReadlineError::Interrupted) => {
+                source.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Failed to read line: {}", err);
+                break;
+            }
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+
+        let line = line.as_str();
+        if source.is_empty() {
+            if let Some(command) = line.trim_start().strip_prefix(':') {
+                run_command(command, options, &mut interpreter);
+                continue;
+            }
+        }
+
+        // A blank continuation line gives up waiting and reports what is wrong
+        let give_up = !source.is_empty() && line.trim().is_empty();
+        if !give_up {
+            if !source.is_empty() {
+                source.push('\n');
+            }
+            source.push_str(line);
+
+            if is_incomplete(&source) {
+                continue;
+            }
+        }
+
+        let _ = run(&source, None, options, &mut interpreter, true);
+        source.clear();
+    }
+
+    if let Some(path) = &history {
+        let _ = editor.save_history(path);
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(HISTORY_FILE))
+}
+
+/// Completes keywords and global names and colors input for the line editor.
+struct LoxHelper {
+    globals: Rc<RefCell<Environment>>,
+}
+
+impl Completer for LoxHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        // Complete the identifier the cursor is at the end of
+        let start = line[..pos]
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(0, |index| index + 1);
+        let prefix = &line[start..pos];
+        if prefix.is_empty() {
+            return Ok((start, Vec::new()));
+        }
+
+        let mut candidates: Vec<String> = KEYWORDS
+            .iter()
+            .map(|(keyword, _)| keyword.to_string())
+            .chain(self.globals.borrow().bindings().into_iter().map(|(name, _)| name))
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for LoxHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LoxHelper {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        if use_color(&io::stdout()) {
+            Cow::Owned(highlight_source(line))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    // Any edit can change how the rest of the line scans, e.g. opening a string
+    fn highlight_char(&self, _: &str, _: usize, _: CmdKind) -> bool {
+        true
+    }
+}
+
+impl Validator for LoxHelper {}
+
+impl Helper for LoxHelper {}
+
+/// Runs a `:` command, `command` being the text after the colon.
+fn run_command(command: &str, options: &Options, interpreter: &mut Interpreter) {
+    let (name, argument) = match command.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (command.trim_end(), ""),
+    };
+
+    match name {
+        "tokens" | "ast" => {
+            let options = Options {
+                command: if name == "tokens" { Command::Tokens } else { Command::Ast },
+                error_format: options.error_format,
+            };
+            let _ = run(argument, None, &options, interpreter, true);
+        }
+        "env" => {
+            for (name, value) in interpreter.globals().borrow().bindings() {
+                println!("{} = {}", name, value);
+            }
+        }
+        "load" => match fs::read_to_string(argument) {
+            Ok(source) => {
+                let _ = run(&source, Some(argument), options, interpreter, false);
+            }
+            Err(err) => eprintln!("Could not read '{}': {}", argument, err),
+        },
+        "reset" => *interpreter = Interpreter::new(),
+        "time" => {
+            let start = Instant::now();
+            let _ = run(argument, None, options, interpreter, true);
+            println!("took {:?}", start.elapsed());
+        }
+        "help" => println!("{}", HELP),
+        _ => println!("Unknown command ':{}'. Type :help for a list.", name),
+    }
+}
+
+/// Whether `source` could still become a valid program if more lines were typed.
+fn is_incomplete(source: &str) -> bool {
+    let scanner = Scanner {
+        source: source.to_owned(),
+    };
+    let (tokens, scan_errs) = scanner.scan_tokens();
+    if scan_errs.iter().any(|scan_err| scan_err.at_eof) {
+        return true;
+    }
+
+    let depth = tokens.iter().fold(0, |depth: i32, token| match token.token_type {
+        TokenType::LeftParen | TokenType::LeftBrace => depth + 1,
+        TokenType::RightParen | TokenType::RightBrace => depth - 1,
+        _ => depth,
+    });
+    if depth > 0 {
+        return true;
+    }
+
+    match Parser::new(tokens).parse_prompt() {
+        Ok(_) => false,
+        Err(parser_errs) => parser_errs.iter().any(|parser_err| parser_err.at_eof),
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use crate::ast::tree::*;
+use crate::interpreter::{node_key, Interpreter};
+use crate::parser::ParserErr;
+use crate::scanner::token::Token;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Static pass that binds every local variable reference to the scope it
+/// refers to, so the interpreter doesn't have to search for it at runtime.
+pub struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+    // Each scope maps a name to whether its initializer has finished resolving
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    pub errors: Vec<ParserErr>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Self {
+            interpreter,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.process_stmt(statement, None);
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let key = name.lexeme.clone().unwrap_or_default();
+        if let Some(scope) = self.scopes.last_mut() {
+            if scope.contains_key(&key) {
+                self.errors.push(ParserErr::new(
+                    name,
+                    String::from("Already a variable with this name in this scope."),
+                ));
+            }
+            scope.insert(key, false);
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone().unwrap_or_default(), true);
+        }
+    }
+
+    fn resolve_local(&mut self, key: usize, name: &Token) {
+        let lexeme = name.lexeme.as_deref().unwrap_or("_");
+        let depth = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(lexeme));
+        // Not found in any local scope means the variable is global
+        self.interpreter.resolve(key, depth);
+    }
+
+    fn resolve_function(&mut self, function: &Function, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+}
+
+impl AstVisitor<(), ()> for Resolver<'_> {
+    fn process(&mut self, expr: &Expr, context: Option<&()>) {
+        expr.accept(self, context)
+    }
+
+    fn visit_binary(&mut self, binary: &Binary, context: Option<&()>) {
+        self.process(&binary.left, context);
+        self.process(&binary.right, context);
+    }
+    fn visit_unary(&mut self, unary: &Unary, context: Option<&()>) {
+        self.process(&unary.right, context);
+    }
+    fn visit_grouping(&mut self, grouping: &Grouping, context: Option<&()>) {
+        self.process(&grouping.expression, context);
+    }
+    fn visit_literal(&mut self, _: &Literal, _: Option<&()>) {}
+    fn visit_variable(&mut self, variable: &Variable, _: Option<&()>) {
+        let lexeme = variable.name.lexeme.as_deref().unwrap_or("_");
+        if let Some(false) = self.scopes.last().and_then(|scope| scope.get(lexeme)) {
+            self.errors.push(ParserErr::new(
+                &variable.name,
+                String::from("Can't read local variable in its own initializer."),
+            ));
+        }
+
+        self.resolve_local(node_key(variable), &variable.name);
+    }
+    fn visit_assign(&mut self, assign: &Assign, context: Option<&()>) {
+        self.process(&assign.value, context);
+        self.resolve_local(node_key(assign), &assign.name);
+    }
+    fn visit_logical(&mut self, logical: &Logical, context: Option<&()>) {
+        self.process(&logical.left, context);
+        self.process(&logical.right, context);
+    }
+    fn visit_call(&mut self, call: &Call, context: Option<&()>) {
+        self.process(&call.callee, context);
+        for argument in &call.arguments {
+            self.process(argument, context);
+        }
+    }
+    fn visit_get(&mut self, get: &Get, context: Option<&()>) {
+        // Properties are looked up dynamically, so only the object is resolved
+        self.process(&get.object, context);
+    }
+    fn visit_set(&mut self, set: &Set, context: Option<&()>) {
+        self.process(&set.value, context);
+        self.process(&set.object, context);
+    }
+    fn visit_this(&mut self, this: &This, _: Option<&()>) {
+        if self.current_class == ClassType::None {
+            self.errors.push(ParserErr::new(
+                &this.keyword,
+                String::from("Can't use 'this' outside of a class."),
+            ));
+            return;
+        }
+
+        self.resolve_local(node_key(this), &this.keyword);
+    }
+    fn visit_super(&mut self, sup: &Super, _: Option<&()>) {
+        match self.current_class {
+            ClassType::None => self.errors.push(ParserErr::new(
+                &sup.keyword,
+                String::from("Can't use 'super' outside of a class."),
+            )),
+            ClassType::Class => self.errors.push(ParserErr::new(
+                &sup.keyword,
+                String::from("Can't use 'super' in a class with no superclass."),
+            ).with_note("Declare a superclass with `class Name < Base`.")),
+            ClassType::Subclass => self.resolve_local(node_key(sup), &sup.keyword),
+        }
+    }
+}
+
+impl StmtVisitor<(), ()> for Resolver<'_> {
+    fn process_stmt(&mut self, stmt: &Stmt, context: Option<&()>) {
+        stmt.accept(self, context)
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression, context: Option<&()>) {
+        self.process(&stmt.expression, context);
+    }
+    fn visit_print_stmt(&mut self, stmt: &Print, context: Option<&()>) {
+        self.process(&stmt.expression, context);
+    }
+    fn visit_var_stmt(&mut self, stmt: &Var, context: Option<&()>) {
+        // Declare first so the initializer can't see the variable it initializes
+        self.declare(&stmt.name);
+        if let Some(initializer) = &stmt.initializer {
+            self.process(initializer, context);
+        }
+        self.define(&stmt.name);
+    }
+    fn visit_block_stmt(&mut self, stmt: &Block, _: Option<&()>) {
+        self.begin_scope();
+        self.resolve(&stmt.statements);
+        self.end_scope();
+    }
+    fn visit_if_stmt(&mut self, stmt: &If, context: Option<&()>) {
+        self.process(&stmt.condition, context);
+        self.process_stmt(&stmt.then_branch, context);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.process_stmt(else_branch, context);
+        }
+    }
+    fn visit_while_stmt(&mut self, stmt: &While, context: Option<&()>) {
+        self.process(&stmt.condition, context);
+        self.process_stmt(&stmt.body, context);
+    }
+    fn visit_function_stmt(&mut self, stmt: &Function, _: Option<&()>) {
+        // Define eagerly so the function can refer to itself recursively
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        self.resolve_function(stmt, FunctionType::Function);
+    }
+    fn visit_return_stmt(&mut self, stmt: &Return, context: Option<&()>) {
+        if self.current_function == FunctionType::None {
+            self.errors.push(ParserErr::new(
+                &stmt.keyword,
+                String::from("Can't return from top-level code."),
+            ));
+        }
+
+        if let Some(value) = &stmt.value {
+            if self.current_function == FunctionType::Initializer {
+                self.errors.push(ParserErr::new(
+                    &stmt.keyword,
+                    String::from("Can't return a value from an initializer."),
+                ).with_note("An initializer always returns `this`; use a bare `return;`."));
+            }
+
+            self.process(value, context);
+        }
+    }
+    fn visit_class_stmt(&mut self, stmt: &Class, context: Option<&()>) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        if let Some(superclass) = &stmt.superclass {
+            if superclass.name.lexeme == stmt.name.lexeme {
+                self.errors.push(ParserErr::new(
+                    &superclass.name,
+                    String::from("A class can't inherit from itself."),
+                ));
+            }
+
+            self.current_class = ClassType::Subclass;
+            self.visit_variable(superclass, context);
+
+            self.begin_scope();
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.insert(String::from("super"), true);
+            }
+        }
+
+        // Methods close over a scope that binds `this`
+        self.begin_scope();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(String::from("this"), true);
+        }
+
+        for method in &stmt.methods {
+            let function_type = match method.name.lexeme.as_deref() {
+                Some("init") => FunctionType::Initializer,
+                _ => FunctionType::Method,
+            };
+            self.resolve_function(method, function_type);
+        }
+
+        self.end_scope();
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::tests::run;
+
+    #[test]
+    fn closures_bind_to_the_scope_they_were_declared_in() {
+        let source = r#"
+            var a = "global";
+            {
+                fun showA() {
+                    print a;
+                }
+
+                showA();
+                var a = "block";
+                showA();
+            }
+        "#;
+        assert_eq!(run(source), "global\nglobal\n");
+    }
+}
@@ -1,22 +1,52 @@
-use super::token::{Token, TokenType};
+use super::token::{Span, Token, TokenType};
 use std::iter::from_fn;
 
+/// Reserved words and the token each one scans to.
+pub const KEYWORDS: [(&str, TokenType); 16] = [
+    ("and", TokenType::And),
+    ("class", TokenType::Class),
+    ("else", TokenType::Else),
+    ("false", TokenType::False),
+    ("for", TokenType::For),
+    ("fun", TokenType::Fun),
+    ("if", TokenType::If),
+    ("nil", TokenType::Nil),
+    ("or", TokenType::Or),
+    ("print", TokenType::Print),
+    ("return", TokenType::Return),
+    ("super", TokenType::Super),
+    ("this", TokenType::This),
+    ("true", TokenType::True),
+    ("var", TokenType::Var),
+    ("while", TokenType::While),
+];
+
 pub struct Scanner {
     pub source: String,
 }
 
 pub struct ScanErr {
-    pub line: usize,
+    pub span: Span,
     pub message: String,
+    // The input ran out, so more of it might fix the error
+    pub at_eof: bool,
 }
 
 impl Scanner {
-    pub fn scan_tokens(self) -> Result<Vec<Token>, ScanErr> {
+    /// Scans the whole source, collecting every lexical error along the way.
+    /// Each bad lexeme is kept in the stream as a `TokenType::Error` token.
+    pub fn scan_tokens(self) -> (Vec<Token>, Vec<ScanErr>) {
         let mut tokens: Vec<Token> = Vec::new();
+        let mut errors: Vec<ScanErr> = Vec::new();
         let mut line: usize = 1;
+        // Column of the next unscanned char, restarting at 1 after each '\n'
+        let mut next_column: usize = 1;
         let mut char_indices = self.source.char_indices().peekable();
 
         while let Some((pos, c)) = char_indices.next() {
+            let start_line = line;
+            let column = next_column;
+
             let (token, lexeme): (TokenType, String) = match c {
                 '(' => (TokenType::LeftParen, c.to_string()),
                 ')' => (TokenType::RightParen, c.to_string()),
@@ -46,12 +76,8 @@ impl Scanner {
                 },
                 '/' => match char_indices.next_if_eq(&(pos + 1, '/')) {
                     Some(_) => {
-                        let s: String = char_indices
-                            .by_ref()
-                            .take_while(|(_pos, c)| {
-                                // Take until we reached a newline char
-                                *c != '\n'
-                            })
+                        // Take until we reach a newline char, leaving it for the whitespace branch
+                        let s: String = from_fn(|| char_indices.by_ref().next_if(|(_pos, c)| *c != '\n'))
                             .map(|(_pos, c)| c)
                             .collect();
                         (TokenType::Comment(s.clone()), format!("//{}", s))
@@ -60,7 +86,7 @@ impl Scanner {
                 },
                 ' ' | '\r' | '\t' | '\n' => {
                     if c == '\n' {
-                        line = line + 1;
+                        line += 1;
                     }
                     (TokenType::Ignore, c.to_string())
                 }
@@ -70,6 +96,10 @@ impl Scanner {
                         .by_ref()
                         .take_while(|(_pos, c)| {
                             last_matched = *c;
+                            // Strings may span several lines
+                            if *c == '\n' {
+                                line += 1;
+                            }
                             *c != '"'
                         })
                         .map(|(_pos, c)| c)
@@ -78,34 +108,35 @@ impl Scanner {
                     match last_matched {
                         '"' => (TokenType::StringLiteral(s.clone()), format!("\"{}\"", s)),
                         _ => {
-                            return Err(ScanErr {
-                                line: line,
+                            // Point at the opening quote, the rest of the file is the string
+                            errors.push(ScanErr {
+                                span: Span {
+                                    start: pos,
+                                    end: pos + 1,
+                                    line: start_line,
+                                    column,
+                                },
                                 message: String::from("Unterminated string."),
-                            })
+                                at_eof: true,
+                            });
+                            (TokenType::Error, format!("\"{}", s))
                         }
                     }
                 }
-                x if x.is_digit(10) => {
-                    let mut number_str: String = x.to_string();
-                    let integral: String =
-                        from_fn(|| char_indices.by_ref().next_if(|(_pos, c)| c.is_digit(10)))
-                            .map(|(_pos, c)| c)
-                            .collect();
-
-                    // Extract the fractional part of the number
-                    let fractional = match char_indices.by_ref().next_if(|(_pos, c)| (*c) == '.') {
-                        Some(_) => {
-                            from_fn(|| char_indices.by_ref().next_if(|(_pos, c)| c.is_digit(10)))
-                                .map(|(_pos, c)| c)
-                                .collect()
+                x if x.is_ascii_digit() => {
+                    from_fn(|| char_indices.by_ref().next_if(|(_pos, c)| c.is_ascii_digit())).for_each(drop);
+
+                    // Only take the fractional part if a digit follows the '.', so `1.foo` stays a property access
+                    let mut lookahead = char_indices.clone();
+                    if let (Some((_, '.')), Some((_, d))) = (lookahead.next(), lookahead.next()) {
+                        if d.is_ascii_digit() {
+                            char_indices.next();
+                            from_fn(|| char_indices.by_ref().next_if(|(_pos, c)| c.is_ascii_digit())).for_each(drop);
                         }
-                        None => "0".to_owned(),
-                    };
+                    }
 
-                    number_str.push_str(&integral);
-                    number_str.push_str(".");
-                    number_str.push_str(&fractional);
-                    // FIXME: This is not correct as the fractional part are '.0' by default
+                    let end = char_indices.peek().map_or(self.source.len(), |(pos, _c)| *pos);
+                    let number_str = self.source[pos..end].to_string();
                     (TokenType::Number(number_str.parse().unwrap()), number_str)
                 }
                 x if Self::is_alpha(x) => {
@@ -119,65 +150,60 @@ impl Scanner {
                     .collect();
                     identifier.push_str(&rest);
 
-                    match identifier.as_str() {
-                        // Reserved keywords
-                        "and" => (TokenType::And, identifier),
-                        "class" => (TokenType::Class, identifier),
-                        "else" => (TokenType::Else, identifier),
-                        "false" => (TokenType::False, identifier),
-                        "for" => (TokenType::For, identifier),
-                        "fun" => (TokenType::Fun, identifier),
-                        "if" => (TokenType::If, identifier),
-                        "nil" => (TokenType::Nil, identifier),
-                        "or" => (TokenType::Or, identifier),
-                        "print" => (TokenType::Print, identifier),
-                        "return" => (TokenType::Return, identifier),
-                        "super" => (TokenType::Super, identifier),
-                        "this" => (TokenType::This, identifier),
-                        "true" => (TokenType::True, identifier),
-                        "var" => (TokenType::Var, identifier),
-                        "while" => (TokenType::While, identifier),
+                    match KEYWORDS.iter().find(|(keyword, _)| *keyword == identifier) {
+                        Some((_, token_type)) => (token_type.clone(), identifier),
                         // Return an identifier if not reserved keyword
-                        _ => (TokenType::Identifier(identifier.clone()), identifier),
+                        None => (TokenType::Identifier(identifier.clone()), identifier),
                     }
                 }
                 _ => {
-                    return Err(ScanErr {
-                        line: line,
+                    errors.push(ScanErr {
+                        span: Span {
+                            start: pos,
+                            end: pos + c.len_utf8(),
+                            line,
+                            column,
+                        },
                         message: format!("Unexpected token: {}", c),
-                    })
+                        at_eof: false,
+                    });
+                    (TokenType::Error, c.to_string())
                 }
             };
 
+            let end = char_indices.peek().map_or(self.source.len(), |(pos, _c)| *pos);
+            for c in self.source[pos..end].chars() {
+                next_column = if c == '\n' { 1 } else { next_column + 1 };
+            }
+
             if let TokenType::Ignore = token {
                 continue;
             }
 
-            let is_comment = if let TokenType::Comment(_) = token {
-                true
-            } else {
-                false
-            };
-
             tokens.push(Token {
                 token_type: token,
                 lexeme: Some(lexeme),
-                line: line,
+                span: Span {
+                    start: pos,
+                    end,
+                    line: start_line,
+                    column,
+                },
             });
-
-            if is_comment {
-                // The comment should be all on 1 line, so add 1 more line
-                line = line + 1;
-            }
         }
 
         tokens.push(Token {
             token_type: TokenType::Eof,
             lexeme: None,
-            line: line,
+            span: Span {
+                start: self.source.len(),
+                end: self.source.len(),
+                line,
+                column: next_column,
+            },
         });
 
-        Ok(tokens)
+        (tokens, errors)
     }
 
     fn is_alpha(c: char) -> bool {
@@ -185,6 +211,6 @@ impl Scanner {
     }
 
     fn is_alpha_numeric(c: char) -> bool {
-        Self::is_alpha(c) || c.is_digit(10)
+        Self::is_alpha(c) || c.is_ascii_digit()
     }
 }
@@ -51,63 +51,86 @@ pub enum TokenType {
     Eof,
     Comment(String),
     Ignore,
+    Error,
 }
 
-impl TokenType {
-    fn to_string(&self) -> String {
-        let result: String;
-        match self {
-            TokenType::LeftParen => result = String::from("LeftParen"),
-            TokenType::RightParen => result = String::from("RightParen"),
-            TokenType::LeftBrace => result = String::from("LeftBrace"),
-            TokenType::RightBrace => result = String::from("RightBrace"),
-            TokenType::Comma => result = String::from("Comma"),
-            TokenType::Dot => result = String::from("Dot"),
-            TokenType::Minus => result = String::from("Minus"),
-            TokenType::Plus => result = String::from("Plus"),
-            TokenType::Semicolon => result = String::from("Semicolon"),
-            TokenType::Slash => result = String::from("Slash"),
-            TokenType::Star => result = String::from("Star"),
-            TokenType::Bang => result = String::from("Bang"),
-            TokenType::BangEqual => result = String::from("BangEqual"),
-            TokenType::Equal => result = String::from("Equal"),
-            TokenType::EqualEqual => result = String::from("EqualEqual"),
-            TokenType::Greater => result = String::from("Greater"),
-            TokenType::GreaterEqual => result = String::from("GreaterEqual"),
-            TokenType::Less => result = String::from("Less"),
-            TokenType::LessEqual => result = String::from("LessEqual"),
-            TokenType::Identifier(val) => result = format!("Identifier({})", val),
-            TokenType::StringLiteral(val) => result = format!("StringLiteral({})", val),
-            TokenType::Number(val) => result = format!("Number({})", val),
-            TokenType::And => result = String::from("And"),
-            TokenType::Class => result = String::from("Class"),
-            TokenType::Else => result = String::from("Else"),
-            TokenType::False => result = String::from("False"),
-            TokenType::Fun => result = String::from("Fun"),
-            TokenType::For => result = String::from("For"),
-            TokenType::If => result = String::from("If"),
-            TokenType::Nil => result = String::from("Nil"),
-            TokenType::Or => result = String::from("Or"),
-            TokenType::Print => result = String::from("Print"),
-            TokenType::Return => result = String::from("Return"),
-            TokenType::Super => result = String::from("Super"),
-            TokenType::This => result = String::from("This"),
-            TokenType::True => result = String::from("True"),
-            TokenType::Var => result = String::from("Var"),
-            TokenType::While => result = String::from("While"),
-            TokenType::Eof => result = String::from("Eof"),
-            TokenType::Comment(val) => result = format!("Comment({})", val),
-            _ => result = String::from("Unknown"),
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result: String = match self {
+            TokenType::LeftParen => String::from("LeftParen"),
+            TokenType::RightParen => String::from("RightParen"),
+            TokenType::LeftBrace => String::from("LeftBrace"),
+            TokenType::RightBrace => String::from("RightBrace"),
+            TokenType::Comma => String::from("Comma"),
+            TokenType::Dot => String::from("Dot"),
+            TokenType::Minus => String::from("Minus"),
+            TokenType::Plus => String::from("Plus"),
+            TokenType::Semicolon => String::from("Semicolon"),
+            TokenType::Slash => String::from("Slash"),
+            TokenType::Star => String::from("Star"),
+            TokenType::Bang => String::from("Bang"),
+            TokenType::BangEqual => String::from("BangEqual"),
+            TokenType::Equal => String::from("Equal"),
+            TokenType::EqualEqual => String::from("EqualEqual"),
+            TokenType::Greater => String::from("Greater"),
+            TokenType::GreaterEqual => String::from("GreaterEqual"),
+            TokenType::Less => String::from("Less"),
+            TokenType::LessEqual => String::from("LessEqual"),
+            TokenType::Identifier(val) => format!("Identifier({})", val),
+            TokenType::StringLiteral(val) => format!("StringLiteral({})", val),
+            TokenType::Number(val) => format!("Number({})", val),
+            TokenType::And => String::from("And"),
+            TokenType::Class => String::from("Class"),
+            TokenType::Else => String::from("Else"),
+            TokenType::False => String::from("False"),
+            TokenType::Fun => String::from("Fun"),
+            TokenType::For => String::from("For"),
+            TokenType::If => String::from("If"),
+            TokenType::Nil => String::from("Nil"),
+            TokenType::Or => String::from("Or"),
+            TokenType::Print => String::from("Print"),
+            TokenType::Return => String::from("Return"),
+            TokenType::Super => String::from("Super"),
+            TokenType::This => String::from("This"),
+            TokenType::True => String::from("True"),
+            TokenType::Var => String::from("Var"),
+            TokenType::While => String::from("While"),
+            TokenType::Eof => String::from("Eof"),
+            TokenType::Comment(val) => format!("Comment({})", val),
+            TokenType::Error => String::from("Error"),
+            _ => String::from("Unknown"),
         };
 
-        result
+        write!(f, "{}", result)
+    }
+}
+
+/// A range of the source, as byte offsets plus the 1-based line and column it starts at.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// Covers everything from the start of `self` to the end of `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end.max(self.end),
+            line: self.line,
+            column: self.column,
+        }
     }
 }
 
+#[derive(Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: Option<String>,
-    pub line: usize,
+    pub span: Span,
 }
 
 impl fmt::Display for Token {